@@ -1,22 +1,99 @@
+use clap::builder::BoolishValueParser;
 use clap::{Parser, Subcommand};
-use possum_lib::model::{self, DownloadRequest};
+use possum_lib::auth;
+use possum_lib::model::{
+    self, cache, download, lock, BandwidthLimiter, CancellationToken, DownloadPlan, DownloadReport,
+    DownloadRequest, FileOutcome, LocalState, Lockfile, Manifest, RetryPolicy, StorageLayout,
+    SyncedModel, TreeEntry,
+};
+use possum_lib::HubClient;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
+use std::io::{BufRead, IsTerminal};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
 
 const DEFAULT_DOWNLOAD_DIR: &str = "./huggingface";
 
+const EXIT_CODES: &str = "\
+Exit codes:
+  0   success
+  1   any other error
+  2   invalid arguments (including invalid globs, manifests and TLS/proxy settings)
+  3   repository, revision or file not found
+  4   unauthorized (a valid token is required)
+  5   gated repository (accept its terms on the hub)
+  6   rate limited
+  7   network or HTTP error
+  8   integrity check failed
+  9   file system error
+  10  some files failed to download
+  11  not enough disk space
+  12  invalid lockfile, or the repository no longer matches it
+  13  offline, and the selected files aren't all on disk
+  130 cancelled (Ctrl-C); run the command again to resume";
+
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, after_help = EXIT_CODES)]
 struct Args {
     #[command(subcommand)]
     command: Option<Commands>,
 
-    /// Base URL for HuggingFace API (for testing)
+    /// Base URL(s) of the Hugging Face hub, comma-separated in order of
+    /// preference: requests fall back to the next (e.g. from an internal
+    /// mirror to the public hub) when one can't be reached or answers 5xx.
+    /// Defaults to $HF_ENDPOINT, then $HUGGINGFACE_API_BASE_URL, then
+    /// https://huggingface.co
+    #[arg(
+        long,
+        global = true,
+        env = "HF_ENDPOINT",
+        value_delimiter = ',',
+        value_name = "URL"
+    )]
+    api_base_url: Vec<String>,
+
+    /// Hugging Face token (needed for private and 'gated' repositories).
+    /// Defaults to $HF_TOKEN, $HUGGING_FACE_HUB_TOKEN, then the token stored
+    /// by `possum auth login` ($HF_TOKEN_PATH or $HF_HOME/token)
+    #[arg(long, global = true)]
+    token: Option<String>,
+
+    /// Send every request through this proxy (default: $HTTPS_PROXY,
+    /// $HTTP_PROXY or $ALL_PROXY; $NO_PROXY is honoured either way)
+    #[arg(long, global = true, value_name = "URL")]
+    proxy: Option<String>,
+
+    /// Also trust the root certificates in this PEM file (e.g. a private CA)
+    #[arg(long, global = true, value_name = "PEM")]
+    ca_bundle: Option<PathBuf>,
+
+    /// Present this client certificate (PEM) to servers that ask for one
+    #[arg(long, global = true, value_name = "PEM", requires = "client_key")]
+    client_cert: Option<PathBuf>,
+
+    /// The PKCS#8 private key (PEM) of --client-cert
+    #[arg(long, global = true, value_name = "PEM", requires = "client_cert")]
+    client_key: Option<PathBuf>,
+
+    /// Seconds to wait for a connection to be established
+    #[arg(long, global = true, value_name = "SECS")]
+    connect_timeout: Option<u64>,
+
+    /// Seconds a response may go without delivering any data
+    #[arg(long, global = true, value_name = "SECS")]
+    read_timeout: Option<u64>,
+
+    /// Never contact the hub: downloads and syncs succeed only if everything
+    /// selected is already on disk, as recorded by earlier downloads
     #[arg(
         long,
-        env = "HUGGINGFACE_API_BASE_URL",
-        default_value = "https://huggingface.co"
+        global = true,
+        env = "HF_HUB_OFFLINE",
+        value_parser = BoolishValueParser::new()
     )]
-    api_base_url: String,
+    offline: bool,
 }
 
 #[derive(Subcommand)]
@@ -27,6 +104,37 @@ enum Commands {
         #[command(subcommand)]
         command: ModelCommands,
     },
+    /// Manage the stored Hugging Face token
+    Auth {
+        #[command(subcommand)]
+        command: AuthCommands,
+    },
+    /// Download every model of a manifest (TOML) together
+    Sync {
+        /// The manifest: `[[models]]` entries with `repository` and optional
+        /// `revision`, `include`, `exclude` and `to`
+        manifest: std::path::PathBuf,
+
+        /// Maximum number of concurrent file downloads, across all models
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+
+        /// Attempts per file before giving up on transient errors (5xx, 429,
+        /// timeouts, dropped connections)
+        #[arg(long, default_value_t = 5)]
+        max_attempts: u32,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AuthCommands {
+    /// Check a token and store it ($HF_TOKEN_PATH or $HF_HOME/token); the
+    /// token is read from `--token` or else from stdin
+    Login,
+    /// Remove the stored token
+    Logout,
+    /// Show the account the token belongs to
+    Whoami,
 }
 
 #[derive(Subcommand, Debug)]
@@ -34,8 +142,13 @@ enum ModelCommands {
     /// Download repository files
     Download {
         /// The model repository-id (e.g. TheBloke/Llama-2-7B-Chat-GPTQ)
-        #[arg(long)]
-        repository: String,
+        #[arg(long, required_unless_present = "locked")]
+        repository: Option<String>,
+
+        /// Fetch exactly the files of a lockfile (see `possum model lock`),
+        /// from its commit, failing if any no longer matches
+        #[arg(long, conflicts_with_all = ["repository", "revision", "include", "exclude"])]
+        locked: Option<std::path::PathBuf>,
 
         /// Optional revision (e.g. gptq-4bit-64g-actorder_True)
         #[arg(short, long)]
@@ -45,10 +158,6 @@ enum ModelCommands {
         #[arg(short, long, default_value = DEFAULT_DOWNLOAD_DIR)]
         to: Option<std::path::PathBuf>,
 
-        /// Hugging Face token (might be needed for 'gated' models)
-        #[arg(long)]
-        token: Option<String>,
-
         /// Glob(s) of files to include (default: all files)
         #[arg(long, num_args = 1..)]
         include: Vec<String>,
@@ -60,6 +169,53 @@ enum ModelCommands {
         /// Maximum number of concurrent file downloads
         #[arg(long, default_value_t = 4)]
         concurrency: usize,
+
+        /// Attempts per file before giving up on transient errors (5xx, 429,
+        /// timeouts, dropped connections)
+        #[arg(long, default_value_t = 5)]
+        max_attempts: u32,
+
+        /// Files larger than this are fetched as concurrent byte-range
+        /// segments of this size (e.g. `64MiB`; `0` never segments)
+        #[arg(long, default_value = "64MiB", value_parser = parse_bytes)]
+        segment_size: u64,
+
+        /// Maximum number of concurrent segment requests per file
+        #[arg(long, default_value_t = 4)]
+        connections_per_file: usize,
+
+        /// Cap the combined download rate of all files (e.g. `50MiB/s`)
+        #[arg(long, value_parser = parse_rate)]
+        max_bandwidth: Option<u64>,
+
+        /// Store files in the Hugging Face hub cache ($HF_HUB_CACHE, $HF_HOME/hub
+        /// or ~/.cache/huggingface/hub) instead of `--to`
+        #[arg(long, conflicts_with = "to")]
+        hf_cache: bool,
+
+        /// Download every selected file, even those already here and up to
+        /// date
+        #[arg(long)]
+        force: bool,
+
+        /// After a successful download, delete the files an earlier download
+        /// put in the directory that are no longer selected
+        #[arg(long, conflicts_with = "hf_cache")]
+        prune: bool,
+
+        /// Show what would be downloaded (and what is already here) without
+        /// downloading anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// With `--dry-run`, print the plan as JSON
+        #[arg(long, requires = "dry_run")]
+        json: bool,
+
+        /// Also write the download report (every file's outcome, bytes and
+        /// time) to this file as JSON
+        #[arg(long = "report", value_name = "PATH", conflicts_with = "dry_run")]
+        report_path: Option<std::path::PathBuf>,
     },
     /// Get repository metadata
     Metadata {
@@ -84,48 +240,447 @@ enum ModelCommands {
         #[arg(long)]
         repository: String,
     },
+
+    /// List a repository's files and directories with their sizes
+    Tree {
+        /// The model repository-id (e.g. TheBloke/Llama-2-7B-Chat-GPTQ)
+        #[arg(long)]
+        repository: String,
+
+        /// Optional revision (e.g. gptq-4bit-64g-actorder_True)
+        #[arg(short, long)]
+        revision: Option<String>,
+
+        /// Print the entries (path, type, size, oid, lfs) as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// With --offline, the directory the repository was downloaded to
+        #[arg(short, long, default_value = DEFAULT_DOWNLOAD_DIR)]
+        to: PathBuf,
+
+        /// With --offline, look in the Hugging Face hub cache instead of `--to`
+        #[arg(long, conflicts_with = "to")]
+        hf_cache: bool,
+    },
+
+    /// Print the size of each file a download would fetch, and the total
+    Du {
+        /// The model repository-id (e.g. TheBloke/Llama-2-7B-Chat-GPTQ)
+        #[arg(long)]
+        repository: String,
+
+        /// Optional revision (e.g. gptq-4bit-64g-actorder_True)
+        #[arg(short, long)]
+        revision: Option<String>,
+
+        /// Glob(s) of files to include (default: all files)
+        #[arg(long, num_args = 1..)]
+        include: Vec<String>,
+
+        /// Glob(s) of files to exclude
+        #[arg(long, num_args = 1..)]
+        exclude: Vec<String>,
+
+        /// With --offline, the directory the repository was downloaded to
+        #[arg(short, long, default_value = DEFAULT_DOWNLOAD_DIR)]
+        to: PathBuf,
+
+        /// With --offline, look in the Hugging Face hub cache instead of `--to`
+        #[arg(long, conflicts_with = "to")]
+        hf_cache: bool,
+    },
+
+    /// Write a lockfile pinning a repository's selected files to a commit,
+    /// with the size and digest of each
+    Lock {
+        /// The model repository-id (e.g. TheBloke/Llama-2-7B-Chat-GPTQ)
+        #[arg(long)]
+        repository: String,
+
+        /// Optional revision (e.g. gptq-4bit-64g-actorder_True)
+        #[arg(short, long)]
+        revision: Option<String>,
+
+        /// Glob(s) of files to include (default: all files)
+        #[arg(long, num_args = 1..)]
+        include: Vec<String>,
+
+        /// Glob(s) of files to exclude
+        #[arg(long, num_args = 1..)]
+        exclude: Vec<String>,
+
+        /// Where to write the lockfile (`-` for stdout)
+        #[arg(short, long, default_value = lock::LOCK_FILE)]
+        output: std::path::PathBuf,
+    },
+}
+
+/// Parse a byte count with an optional unit: `1048576`, `512KB`, `64MiB`,
+/// `1.5GiB` (decimal `KB`/`MB`/`GB`, binary `KiB`/`MiB`/`GiB`).
+fn parse_bytes(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("invalid size '{s}'"))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        _ => return Err(format!("unknown unit in size '{s}'")),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Parse a rate in bytes per second: a byte count as for [`parse_bytes`],
+/// optionally followed by `/s` (`50MiB/s`).
+fn parse_rate(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let rate = parse_bytes(s.strip_suffix("/s").unwrap_or(s))?;
+    if rate == 0 {
+        return Err(format!("rate '{s}' must be above zero"));
+    }
+    Ok(rate)
+}
+
+/// Format a byte count for people: `665 B`, `119.1 MiB`.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Summarize a sync: one line per model, then the totals.
+fn render_sync(synced: &[SyncedModel]) -> String {
+    let width = synced
+        .iter()
+        .map(|s| s.entry.repository.len())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for s in synced {
+        let repository = &s.entry.repository;
+        match &s.result {
+            Ok(report) => out.push_str(&format!(
+                "{:<7} {repository:<width$}  {} file(s) -> {}\n",
+                "ok",
+                report.files.len(),
+                s.to.display()
+            )),
+            Err(possum_lib::Error::Cancelled) => out.push_str(&format!(
+                "{:<7} {repository:<width$}  left unfinished\n",
+                "CANCEL"
+            )),
+            Err(e) => {
+                out.push_str(&format!("{:<7} {repository:<width$}  {e}\n", "FAILED"));
+                if let possum_lib::Error::DownloadFailed { failures, .. } = e {
+                    for (file, error) in failures {
+                        out.push_str(&format!("          {file}: {error}\n"));
+                    }
+                }
+            }
+        }
+    }
+    let ok = synced.iter().filter(|s| s.result.is_ok()).count();
+    out.push_str(&format!("{ok} of {} model(s) synced\n", synced.len()));
+    out
+}
+
+/// Render a download report as a table: one row per file, then the totals.
+fn render_report(report: &DownloadReport) -> String {
+    let mut out = format!("{:<12} {:>10} {:>8}  FILE\n", "STATUS", "FETCHED", "TIME");
+    for file in &report.files {
+        let status = match file.outcome {
+            FileOutcome::Downloaded { .. } => "downloaded",
+            FileOutcome::Skipped { .. } => "skipped",
+            FileOutcome::Failed { .. } => "failed",
+            FileOutcome::Cancelled => "cancelled",
+        };
+        out.push_str(&format!(
+            "{status:<12} {:>10} {:>7.1}s  {}\n",
+            format_bytes(file.bytes),
+            file.duration.as_secs_f64(),
+            file.file
+        ));
+    }
+    for file in &report.pruned {
+        out.push_str(&format!(
+            "{:<12} {:>10} {:>8}  {file}\n",
+            "pruned", "-", "-"
+        ));
+    }
+    out.push_str(&format!(
+        "{} downloaded, {} skipped, {} failed{}; {} in {:.1}s ({}/s) from commit {}{}\n",
+        report.downloaded().count(),
+        report.skipped().count(),
+        report.failed().count(),
+        match report.cancelled().count() {
+            0 => String::new(),
+            n => format!(", {n} cancelled"),
+        },
+        format_bytes(report.bytes),
+        report.duration.as_secs_f64(),
+        format_bytes(report.throughput() as u64),
+        report.commit.as_deref().unwrap_or("unknown"),
+        match report.endpoints.as_slice() {
+            [] => String::new(),
+            endpoints => format!(" at {}", endpoints.join(", ")),
+        }
+    ));
+    out
+}
+
+/// Render a download plan as a table: one row per selected or skipped file.
+fn render_plan(plan: &DownloadPlan) -> String {
+    let mut out = format!(
+        "{} @ {} (commit {})\n",
+        plan.repository,
+        plan.revision,
+        plan.commit.as_deref().unwrap_or("unknown")
+    );
+    out.push_str(&format!("{:<12} {:>10}  FILE\n", "ACTION", "SIZE"));
+    for planned in &plan.files {
+        let action = match planned.local {
+            LocalState::Missing => "fetch",
+            LocalState::Partial { .. } => "resume",
+            LocalState::UpToDate => "up-to-date",
+            LocalState::Stale => "replace",
+        };
+        let size = planned.size.map_or("?".to_string(), format_bytes);
+        out.push_str(&format!("{action:<12} {size:>10}  {}\n", planned.file));
+    }
+    for file in &plan.skipped {
+        out.push_str(&format!("{:<12} {:>10}  {file}\n", "skip", "-"));
+    }
+    for file in &plan.prune {
+        out.push_str(&format!("{:<12} {:>10}  {file}\n", "delete", "-"));
+    }
+    out.push_str(&format!(
+        "{} file(s) selected, {} to fetch",
+        plan.files.len(),
+        format_bytes(plan.bytes_to_fetch())
+    ));
+    if !plan.prune.is_empty() {
+        out.push_str(&format!(", {} file(s) to delete", plan.prune.len()));
+    }
+    out.push('\n');
+    out
+}
+
+/// Render a tree listing as an indented tree; directories show the total
+/// size of the files beneath them.
+fn render_tree(entries: &[TreeEntry]) -> String {
+    let mut children: BTreeMap<&str, Vec<&TreeEntry>> = BTreeMap::new();
+    for entry in entries {
+        let parent = entry.path.rsplit_once('/').map_or("", |(dir, _)| dir);
+        children.entry(parent).or_default().push(entry);
+    }
+    let files: Vec<&TreeEntry> = entries.iter().filter(|e| e.is_file()).collect();
+    let total: u64 = files.iter().map(|f| f.size).sum();
+
+    let mut out = String::new();
+    render_dir("", "", &children, &files, &mut out);
+    out.push_str(&format!(
+        "{} file(s), {}\n",
+        files.len(),
+        format_bytes(total)
+    ));
+    out
+}
+
+fn render_dir(
+    dir: &str,
+    prefix: &str,
+    children: &BTreeMap<&str, Vec<&TreeEntry>>,
+    files: &[&TreeEntry],
+    out: &mut String,
+) {
+    let Some(entries) = children.get(dir) else {
+        return;
+    };
+    let mut entries = entries.clone();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    for (i, entry) in entries.iter().enumerate() {
+        let last = i + 1 == entries.len();
+        let name = entry.path.rsplit('/').next().unwrap_or(&entry.path);
+        let branch = if last { "└── " } else { "├── " };
+        if entry.is_file() {
+            out.push_str(&format!(
+                "{prefix}{branch}{name}  {}\n",
+                format_bytes(entry.size)
+            ));
+        } else {
+            let inside = format!("{}/", entry.path);
+            let size: u64 = files
+                .iter()
+                .filter(|f| f.path.starts_with(&inside))
+                .map(|f| f.size)
+                .sum();
+            out.push_str(&format!(
+                "{prefix}{branch}{name}/  {}\n",
+                format_bytes(size)
+            ));
+            let prefix = format!("{prefix}{}", if last { "    " } else { "│   " });
+            render_dir(&entry.path, &prefix, children, files, out);
+        }
+    }
+}
+
+/// Where a download of `repository` at `revision` goes: `to/<repository>`
+/// (with `:<revision>` appended if one is given), or the hub cache.
+fn destination(
+    repository: &str,
+    revision: Option<&str>,
+    to: &std::path::Path,
+    hf_cache: bool,
+) -> Result<(PathBuf, StorageLayout), Box<dyn Error + Send + Sync>> {
+    if hf_cache {
+        let cache_dir = cache::hub_cache_dir()
+            .ok_or("Cannot locate the Hugging Face cache; set HF_HUB_CACHE or HF_HOME")?;
+        return Ok((cache_dir, StorageLayout::HfCache));
+    }
+    let mut local_dir = to.to_path_buf();
+    local_dir.push(repository);
+    if let Some(rev) = revision {
+        // Convert to a string to append revision
+        let lds = local_dir.to_string_lossy();
+        local_dir = PathBuf::from(format!("{lds}:{rev}"));
+    }
+    Ok((local_dir, StorageLayout::Flat))
+}
+
+/// A request to list `repository`'s files from what a download left at its
+/// destination, without the hub.
+fn offline_request(
+    repository: &str,
+    revision: &Option<String>,
+    to: &std::path::Path,
+    hf_cache: bool,
+) -> Result<DownloadRequest, Box<dyn Error + Send + Sync>> {
+    let (to, layout) = destination(repository, revision.as_deref(), to, hf_cache)?;
+    Ok(DownloadRequest {
+        repository: repository.to_string(),
+        revision: revision.clone(),
+        to,
+        layout,
+        offline: true,
+        ..Default::default()
+    })
 }
 
 async fn model_command(
     command: &ModelCommands,
-    api_base_url: &str,
+    hub: &HubClient,
+    offline: bool,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     match command {
         ModelCommands::Download {
             repository,
+            locked,
             revision,
             to,
-            token,
             include,
             exclude,
             concurrency,
+            max_attempts,
+            segment_size,
+            connections_per_file,
+            max_bandwidth,
+            hf_cache,
+            force,
+            prune,
+            dry_run,
+            json,
+            report_path,
         } => {
-            let mut local_dir = to.as_ref().unwrap().clone();
-            local_dir.push(repository);
-            if let Some(rev) = revision {
-                // Convert to a string to append revision
-                let lds = local_dir.to_string_lossy();
-                local_dir = std::path::PathBuf::from(format!("{lds}:{rev}"));
-            }
+            let lock = locked.as_deref().map(Lockfile::load).transpose()?;
+            // A locked download goes where the unlocked one it was made from
+            // would have.
+            let (repository, revision) = match &lock {
+                Some(lock) => (
+                    &lock.repository,
+                    Some(lock.revision.clone()).filter(|rev| rev != "main"),
+                ),
+                None => (
+                    repository
+                        .as_ref()
+                        .expect("clap requires --repository without --locked"),
+                    revision.clone(),
+                ),
+            };
+            let (local_dir, layout) = destination(
+                repository,
+                revision.as_deref(),
+                to.as_ref().unwrap(),
+                *hf_cache,
+            )?;
             let request = DownloadRequest {
                 repository: repository.clone(),
-                revision: revision.clone(),
+                revision,
                 to: local_dir,
-                token: token.clone(),
                 include: include.clone(),
                 exclude: exclude.clone(),
                 concurrency: *concurrency,
-                api_base_url: api_base_url.to_string(),
+                layout,
+                retry: RetryPolicy {
+                    max_attempts: *max_attempts,
+                    ..Default::default()
+                },
+                segment_size: *segment_size,
+                connections_per_file: *connections_per_file,
+                max_bandwidth: max_bandwidth.map(BandwidthLimiter::new),
+                offline,
+                force: *force,
+                prune: *prune,
+                lock,
                 ..Default::default()
             };
-            model::download(&request).await?;
+            if *dry_run {
+                let plan = hub.plan_download(&request).await?;
+                if *json {
+                    println!("{}", serde_json::to_string(&plan)?);
+                } else {
+                    print!("{}", render_plan(&plan));
+                }
+                return Ok(());
+            }
+            let request = DownloadRequest {
+                cancel: cancel_on_ctrl_c(),
+                ..request
+            };
+            let report = hub.download(&request).await?;
+            print!("{}", render_report(&report));
+            if let Some(path) = report_path {
+                std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
+            }
+            if report.cancelled().next().is_some() {
+                eprintln!("Cancelled; left unfinished (run the same command again to resume):");
+                for file in report.cancelled() {
+                    eprintln!("  {}", file.file);
+                }
+            }
+            report.into_result()?;
         }
         ModelCommands::Metadata { repository } => {
-            let meta = model::metadata(repository, api_base_url).await?;
-            println!("{meta}");
+            let meta = hub.metadata(repository).await?;
+            println!("{}", serde_json::to_string(&meta)?);
         }
         ModelCommands::Search { keyword, filter } => {
-            let ids = model::search(keyword, filter.as_deref(), api_base_url).await?;
+            let ids = model::search::model_ids(&hub.search(keyword, filter.as_deref()).await?);
             if ids.is_empty() {
                 println!("No models found for '{}'.", keyword.join(" "));
             } else {
@@ -135,7 +690,7 @@ async fn model_command(
             }
         }
         ModelCommands::Revisions { repository } => {
-            let names = model::revisions(repository, api_base_url).await?;
+            let names = hub.revisions(repository).await?;
             if names.is_empty() {
                 println!("No branches found.");
             } else {
@@ -144,17 +699,262 @@ async fn model_command(
                 }
             }
         }
+        ModelCommands::Tree {
+            repository,
+            revision,
+            json,
+            to,
+            hf_cache,
+        } => {
+            let entries = if offline {
+                let request = offline_request(repository, revision, to, *hf_cache)?;
+                model::tree_of(&hub.list_download_files(&request).await?)
+            } else {
+                hub.list_tree(repository, revision.as_deref()).await?
+            };
+            if *json {
+                println!("{}", serde_json::to_string(&entries)?);
+            } else {
+                print!("{}", render_tree(&entries));
+            }
+        }
+        ModelCommands::Du {
+            repository,
+            revision,
+            include,
+            exclude,
+            to,
+            hf_cache,
+        } => {
+            let sizes: HashMap<String, u64> = if offline {
+                let request = offline_request(repository, revision, to, *hf_cache)?;
+                hub.list_download_files(&request)
+                    .await?
+                    .into_iter()
+                    .map(|sibling| (sibling.rfilename, sibling.size.unwrap_or_default()))
+                    .collect()
+            } else {
+                hub.list_tree(repository, revision.as_deref())
+                    .await?
+                    .into_iter()
+                    .filter(|entry| entry.is_file())
+                    .map(|entry| (entry.path, entry.size))
+                    .collect()
+            };
+            let files: Vec<String> = sizes.keys().cloned().collect();
+            let has_safetensors = download::has_safetensors_weights(&files);
+            let mut selected = model::select_files(files, include, exclude, has_safetensors)?;
+            selected.sort();
+            let mut total = 0;
+            for file in &selected {
+                total += sizes[file];
+                println!("{:>10}  {file}", format_bytes(sizes[file]));
+            }
+            println!(
+                "{:>10}  total ({} file(s))",
+                format_bytes(total),
+                selected.len()
+            );
+        }
+        ModelCommands::Lock {
+            repository,
+            revision,
+            include,
+            exclude,
+            output,
+        } => {
+            let request = DownloadRequest {
+                repository: repository.clone(),
+                revision: revision.clone(),
+                include: include.clone(),
+                exclude: exclude.clone(),
+                ..Default::default()
+            };
+            let lock = hub.lock(&request).await?;
+            if output.as_os_str() == "-" {
+                print!("{}", lock.to_toml());
+            } else {
+                lock.save(output)?;
+                eprintln!(
+                    "Locked {} file(s) of {} at {} in {}",
+                    lock.files.len(),
+                    lock.repository,
+                    lock.commit,
+                    output.display()
+                );
+            }
+        }
     };
 
     Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
-    let subscriber = tracing_subscriber::FmtSubscriber::new();
-    tracing::subscriber::set_global_default(subscriber)?;
+async fn auth_command(
+    command: &AuthCommands,
+    hub: &HubClient,
+    token: Option<&str>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match command {
+        AuthCommands::Login => {
+            let token = match token {
+                Some(token) => token.to_string(),
+                None => read_token()?,
+            };
+            let me = hub.with_token(Some(&token)).whoami().await?;
+            let path = auth::save_token(&token)?;
+            println!(
+                "Logged in as {}; token saved to {}",
+                me["name"].as_str().unwrap_or("?"),
+                path.display()
+            );
+        }
+        AuthCommands::Logout => match auth::delete_token()? {
+            Some(path) => println!("Removed {}", path.display()),
+            None => println!("Not logged in."),
+        },
+        AuthCommands::Whoami => {
+            if hub.token().is_none() {
+                return Err("Not logged in (run `possum auth login` or set HF_TOKEN)".into());
+            }
+            let me = hub.whoami().await?;
+            println!("{}", me["name"].as_str().unwrap_or("?"));
+            let orgs: Vec<&str> = me["orgs"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|org| org["name"].as_str())
+                .collect();
+            if !orgs.is_empty() {
+                println!("orgs: {}", orgs.join(", "));
+            }
+        }
+    }
 
+    Ok(())
+}
+
+// Read a token from stdin, prompting when it's a terminal (so the token
+// stays out of shell history and process listings).
+fn read_token() -> Result<String, Box<dyn Error + Send + Sync>> {
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprint!("Token: ");
+    }
+    let mut line = String::new();
+    stdin.lock().read_line(&mut line)?;
+    let token = line.trim();
+    if token.is_empty() {
+        return Err("No token given".into());
+    }
+    Ok(token.to_string())
+}
+
+/// The hub endpoints to use: those given (or in $HF_ENDPOINT), else the one
+/// in the older $HUGGINGFACE_API_BASE_URL; none means the public hub.
+fn endpoints(given: &[String]) -> Vec<String> {
+    let given: Vec<String> = given
+        .iter()
+        .map(|e| e.trim().to_string())
+        .filter(|e| !e.is_empty())
+        .collect();
+    if !given.is_empty() {
+        return given;
+    }
+    std::env::var("HUGGINGFACE_API_BASE_URL")
+        .ok()
+        .filter(|e| !e.is_empty())
+        .into_iter()
+        .collect()
+}
+
+/// Whether `command` can only run by talking to the hub (downloads and syncs
+/// can run offline from what's on disk; logging out is local).
+fn needs_hub(command: Option<&Commands>) -> bool {
+    !matches!(
+        command,
+        None | Some(Commands::Sync { .. })
+            | Some(Commands::Model {
+                command: ModelCommands::Download { .. }
+                    | ModelCommands::Tree { .. }
+                    | ModelCommands::Du { .. }
+            })
+            | Some(Commands::Auth {
+                command: AuthCommands::Logout
+            })
+    )
+}
+
+/// A token cancelled on the first Ctrl-C, so a download stops cleanly (its
+/// temporaries kept to resume from) instead of being killed.
+fn cancel_on_ctrl_c() -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let on_signal = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            tracing::warn!("Cancelling; press Ctrl-C again to quit at once");
+            on_signal.cancel();
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }
+    });
+    cancel
+}
+
+/// The process exit code for an error, by the kind of [`possum_lib::Error`]
+/// behind it (see [`EXIT_CODES`]).
+fn exit_code(e: &(dyn Error + 'static)) -> u8 {
+    use possum_lib::Error as E;
+    match e.downcast_ref::<E>() {
+        Some(E::InvalidGlob(_) | E::Manifest(_) | E::Config(_)) => 2,
+        Some(E::NotFound { .. }) => 3,
+        Some(E::Unauthorized { .. }) => 4,
+        Some(E::Gated { .. }) => 5,
+        Some(E::RateLimited { .. }) => 6,
+        Some(E::Network(_) | E::Http { .. } | E::InvalidResponse(_)) => 7,
+        Some(E::Integrity { .. }) => 8,
+        Some(E::Io(_)) => 9,
+        Some(E::DownloadFailed { .. } | E::SyncFailed { .. }) => 10,
+        Some(E::InsufficientSpace { .. }) => 11,
+        Some(E::Lock(_)) => 12,
+        Some(E::NotAvailableOffline { .. }) => 13,
+        Some(E::Cancelled) => 130,
+        None => 1,
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
     let args = Args::parse();
+    match run(&args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            match e.downcast_ref() {
+                Some(possum_lib::Error::DownloadFailed { failures, .. }) => {
+                    for (file, error) in failures {
+                        eprintln!("  {file}: {error}");
+                    }
+                }
+                Some(possum_lib::Error::NotAvailableOffline { missing, .. }) => {
+                    for file in missing {
+                        eprintln!("  missing: {file}");
+                    }
+                }
+                _ => {}
+            }
+            ExitCode::from(exit_code(e.as_ref()))
+        }
+    }
+}
+
+async fn run(args: &Args) -> Result<(), Box<dyn Error + Send + Sync>> {
+    // Logs go to stderr so that stdout carries only the command's output
+    // (e.g. JSON to pipe into `jq`).
+    let subscriber = tracing_subscriber::FmtSubscriber::builder()
+        .with_writer(std::io::stderr)
+        .finish();
+    tracing::subscriber::set_global_default(subscriber)?;
 
     // cargo run --bin possum -- model search --keyword TheBloke Llama-2-7B --filter gptq
     // cargo run --bin possum -- model metadata --repository TheBloke/Llama-2-7B-Chat-GPTQ | jq '.transformersInfo'
@@ -162,8 +962,65 @@ async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     // cargo run --bin possum -- model download --repository TheBloke/Llama-2-7B-Chat-GPTQ --revision gptq-4bit-64g-actorder_True
 
     tracing::info!("Hello possums! ✨");
+    let mut hub = HubClient::builder().endpoints(endpoints(&args.api_base_url));
+    if let Some(token) = auth::resolve_token(args.token.as_deref()) {
+        hub = hub.token(token);
+    }
+    if let Some(proxy) = &args.proxy {
+        hub = hub.proxy(proxy);
+    }
+    if let Some(path) = &args.ca_bundle {
+        hub = hub.ca_bundle(path);
+    }
+    if let (Some(cert), Some(key)) = (&args.client_cert, &args.client_key) {
+        hub = hub.client_cert(cert, key);
+    }
+    if let Some(secs) = args.connect_timeout {
+        hub = hub.connect_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = args.read_timeout {
+        hub = hub.read_timeout(Duration::from_secs(secs));
+    }
+    let hub = hub.build()?;
+    if args.offline && needs_hub(args.command.as_ref()) {
+        return Err("this command needs the hub, but --offline (or HF_HUB_OFFLINE) is set".into());
+    }
     match &args.command {
-        Some(Commands::Model { command }) => model_command(command, &args.api_base_url).await?,
+        Some(Commands::Model { command }) => model_command(command, &hub, args.offline).await?,
+        Some(Commands::Auth { command }) => {
+            auth_command(command, &hub, args.token.as_deref()).await?
+        }
+        Some(Commands::Sync {
+            manifest,
+            concurrency,
+            max_attempts,
+        }) => {
+            let manifest = Manifest::load(manifest)?;
+            let cancel = cancel_on_ctrl_c();
+            let template = DownloadRequest {
+                concurrency: *concurrency,
+                retry: RetryPolicy {
+                    max_attempts: *max_attempts,
+                    ..Default::default()
+                },
+                cancel: cancel.clone(),
+                offline: args.offline,
+                ..Default::default()
+            };
+            let synced = hub.sync(&manifest, &template).await;
+            print!("{}", render_sync(&synced));
+            if cancel.is_cancelled() {
+                return Err(possum_lib::Error::Cancelled.into());
+            }
+            let failed = synced.iter().filter(|s| s.result.is_err()).count();
+            if failed > 0 {
+                return Err(possum_lib::Error::SyncFailed {
+                    failed,
+                    total: synced.len(),
+                }
+                .into());
+            }
+        }
         None => (),
     }
 
@@ -257,21 +1114,41 @@ mod tests {
                 command:
                     ModelCommands::Download {
                         repository,
+                        locked,
                         revision,
                         to,
-                        token,
                         include,
                         exclude,
                         concurrency,
+                        max_attempts,
+                        segment_size,
+                        connections_per_file,
+                        max_bandwidth,
+                        hf_cache,
+                        force,
+                        prune,
+                        dry_run,
+                        json,
+                        report_path,
                     },
             }) => {
-                assert_eq!(repository, "TheBloke/Llama-2-7B-Chat-GPTQ");
+                assert_eq!(repository.as_deref(), Some("TheBloke/Llama-2-7B-Chat-GPTQ"));
+                assert!(locked.is_none());
                 assert_eq!(revision, Some("gptq-4bit-64g-actorder_True".to_string()));
                 assert_eq!(to, Some(std::path::PathBuf::from(DEFAULT_DOWNLOAD_DIR)));
-                assert_eq!(token, None);
                 assert!(include.is_empty());
                 assert!(exclude.is_empty());
                 assert_eq!(concurrency, 4);
+                assert_eq!(max_attempts, 5);
+                assert_eq!(segment_size, 64 * 1024 * 1024);
+                assert_eq!(connections_per_file, 4);
+                assert!(max_bandwidth.is_none());
+                assert!(!hf_cache);
+                assert!(!force);
+                assert!(!prune);
+                assert!(!dry_run);
+                assert!(!json);
+                assert!(report_path.is_none());
             }
             _ => panic!("Expected Download command"),
         }
@@ -296,17 +1173,151 @@ mod tests {
                         repository,
                         revision,
                         to,
-                        token,
                         ..
                     },
             }) => {
-                assert_eq!(repository, "TheBloke/Llama-2-7B-Chat-GPTQ");
+                assert_eq!(repository.as_deref(), Some("TheBloke/Llama-2-7B-Chat-GPTQ"));
                 assert_eq!(revision, None);
                 assert_eq!(to, Some(std::path::PathBuf::from("/custom/path")));
-                assert_eq!(token, None);
             }
             _ => panic!("Expected Download command"),
         }
+        assert_eq!(args.token, None);
+    }
+
+    #[test]
+    fn test_cli_token_is_global() {
+        let args = Args::parse_from([
+            "possum",
+            "model",
+            "metadata",
+            "--repository",
+            "our-org/private-model",
+            "--token",
+            "hf_secret",
+        ]);
+        assert_eq!(args.token, Some("hf_secret".to_string()));
+        assert!(matches!(
+            args.command,
+            Some(Commands::Model {
+                command: ModelCommands::Metadata { .. }
+            })
+        ));
+    }
+
+    #[test]
+    fn test_cli_auth() {
+        let args = Args::parse_from(["possum", "auth", "login", "--token", "hf_secret"]);
+        assert_eq!(args.token, Some("hf_secret".to_string()));
+        assert!(matches!(
+            args.command,
+            Some(Commands::Auth {
+                command: AuthCommands::Login
+            })
+        ));
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(665), "665 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(548_105_171), "522.7 MiB");
+    }
+
+    #[test]
+    fn test_render_tree() {
+        let entries: Vec<TreeEntry> = serde_json::from_str(
+            r#"[
+                {"type": "file", "oid": "a", "size": 665, "path": "config.json"},
+                {"type": "directory", "oid": "b", "size": 0, "path": "onnx"},
+                {"type": "file", "oid": "c", "size": 2048, "path": "onnx/model.onnx"},
+                {"type": "file", "oid": "d", "size": 1024, "path": "onnx/config.json"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            render_tree(&entries),
+            "\
+├── config.json  665 B
+└── onnx/  3.0 KiB
+    ├── config.json  1.0 KiB
+    └── model.onnx  2.0 KiB
+3 file(s), 3.6 KiB
+"
+        );
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let not_found = possum_lib::Error::NotFound {
+            url: "https://huggingface.co/api/models/nope".to_string(),
+        };
+        assert_eq!(exit_code(&not_found), 3);
+        let failed = possum_lib::Error::DownloadFailed {
+            repository: "test/model".to_string(),
+            failures: vec![],
+        };
+        assert_eq!(exit_code(&failed), 10);
+        assert_eq!(exit_code(&possum_lib::Error::Cancelled), 130);
+        assert_eq!(exit_code(&std::fmt::Error), 1);
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("1024"), Ok(1024));
+        assert_eq!(parse_bytes("512KB"), Ok(512_000));
+        assert_eq!(parse_bytes("64MiB"), Ok(64 << 20));
+        assert_eq!(parse_bytes("1.5GiB"), Ok(3 << 29));
+        assert!(parse_bytes("lots").is_err());
+        assert!(parse_bytes("5 parsecs").is_err());
+    }
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("50MiB/s"), Ok(50 << 20));
+        assert_eq!(parse_rate("1MB"), Ok(1_000_000));
+        assert!(parse_rate("0/s").is_err());
+        assert!(parse_rate("fast").is_err());
+    }
+
+    #[test]
+    fn test_cli_http_settings() {
+        let args = Args::parse_from([
+            "possum",
+            "model",
+            "revisions",
+            "--repository",
+            "test/model",
+            "--proxy",
+            "http://proxy.internal:3128",
+            "--ca-bundle",
+            "/etc/ssl/corp.pem",
+            "--connect-timeout",
+            "10",
+        ]);
+        assert_eq!(args.proxy.as_deref(), Some("http://proxy.internal:3128"));
+        assert_eq!(args.ca_bundle, Some(PathBuf::from("/etc/ssl/corp.pem")));
+        assert_eq!(args.connect_timeout, Some(10));
+        assert!(args.read_timeout.is_none());
+
+        // A client certificate needs its key, and vice versa.
+        let args = Args::try_parse_from(["possum", "--client-cert", "/tmp/cert.pem"]);
+        assert!(args.is_err());
+    }
+
+    #[test]
+    fn test_cli_model_download_hf_cache_conflicts_with_to() {
+        let args = Args::try_parse_from([
+            "possum",
+            "model",
+            "download",
+            "--repository",
+            "TheBloke/Llama-2-7B-Chat-GPTQ",
+            "--hf-cache",
+            "--to",
+            "/custom/path",
+        ]);
+        assert!(args.is_err());
     }
 
     #[test]
@@ -1,6 +1,6 @@
 use assert_cmd::Command;
 use serde_json::json;
-use wiremock::matchers::{method, path, query_param};
+use wiremock::matchers::{header, method, path, path_regex, query_param};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
 #[tokio::test]
@@ -197,3 +197,1847 @@ fn test_cli_version_output() {
 
     assert!(output.status.success());
 }
+
+#[tokio::test]
+async fn test_model_download_resumes_incomplete_file() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"siblings": [{"rfilename": "model.safetensors"}]})),
+        )
+        .mount(&mock_server)
+        .await;
+
+    // Only the missing tail is served, and only to a ranged request.
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/main/model.safetensors"))
+        .and(header("range", "bytes=5-"))
+        .respond_with(
+            ResponseTemplate::new(206)
+                .insert_header("content-range", "bytes 5-14/15")
+                .set_body_string("model data"),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let model_dir = temp_dir.path().join("test").join("model");
+    std::fs::create_dir_all(&model_dir).unwrap();
+    std::fs::write(model_dir.join("model.safetensors.incomplete"), "fake ").unwrap();
+
+    let mut cmd = Command::cargo_bin("possum").unwrap();
+    let output = cmd
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "download",
+            "--repository",
+            "test/model",
+            "--to",
+            temp_dir.path().to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(model_dir.join("model.safetensors")).unwrap(),
+        "fake model data"
+    );
+    assert!(!model_dir.join("model.safetensors.incomplete").exists());
+}
+
+#[tokio::test]
+async fn test_model_download_verifies_content() {
+    let mock_server = MockServer::start().await;
+
+    // The git blob id of "{}" and sha256("fake model data"); the server
+    // then hands back different bytes for the weights.
+    let mock_response = json!({
+        "siblings": [
+            {"rfilename": "config.json", "blobId": "9e26dfeeb6e641a33dae4961196235bdb965b21b"},
+            {
+                "rfilename": "model.safetensors",
+                "lfs": {
+                    "sha256": "c4928585ac684a63148634c0655c561d94260f841aceb618ef21b6492e8a1da8",
+                    "size": 15
+                }
+            }
+        ]
+    });
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&mock_response))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/main/config.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/main/model.safetensors"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("corrupted data!"))
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let mut cmd = Command::cargo_bin("possum").unwrap();
+    let output = cmd
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "download",
+            "--repository",
+            "test/model",
+            "--to",
+            temp_dir.path().to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+
+    let model_dir = temp_dir.path().join("test").join("model");
+    assert!(model_dir.join("config.json").exists());
+    assert!(!model_dir.join("model.safetensors").exists());
+    assert!(!model_dir.join("model.safetensors.incomplete").exists());
+}
+
+#[tokio::test]
+async fn test_model_download_into_hf_cache() {
+    let mock_server = MockServer::start().await;
+
+    let mock_response = json!({
+        "sha": "0123456789abcdef0123456789abcdef01234567",
+        "siblings": [
+            {"rfilename": "config.json", "blobId": "9e26dfeeb6e641a33dae4961196235bdb965b21b"}
+        ]
+    });
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&mock_response))
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path(
+            "/test/model/resolve/0123456789abcdef0123456789abcdef01234567/config.json",
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let cache_dir = tempfile::tempdir().unwrap();
+
+    // The second run finds the blob already cached and fetches nothing.
+    for _ in 0..2 {
+        let mut cmd = Command::cargo_bin("possum").unwrap();
+        let output = cmd
+            .env("HF_HUB_CACHE", cache_dir.path())
+            .args([
+                "--api-base-url",
+                &mock_server.uri(),
+                "model",
+                "download",
+                "--repository",
+                "test/model",
+                "--hf-cache",
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+    }
+
+    let repo_dir = cache_dir.path().join("models--test--model");
+    assert_eq!(
+        std::fs::read_to_string(repo_dir.join("refs").join("main")).unwrap(),
+        "0123456789abcdef0123456789abcdef01234567"
+    );
+    assert_eq!(
+        std::fs::read_to_string(
+            repo_dir
+                .join("blobs")
+                .join("9e26dfeeb6e641a33dae4961196235bdb965b21b")
+        )
+        .unwrap(),
+        "{}"
+    );
+    let snapshot = repo_dir
+        .join("snapshots")
+        .join("0123456789abcdef0123456789abcdef01234567")
+        .join("config.json");
+    assert_eq!(std::fs::read_to_string(snapshot).unwrap(), "{}");
+}
+
+#[tokio::test]
+async fn test_model_download_retries_transient_errors() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "siblings": [{"rfilename": "config.json"}, {"rfilename": "missing.json"}]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    // Rate limited once, then served.
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/main/config.json"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+        .up_to_n_times(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/main/config.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .mount(&mock_server)
+        .await;
+
+    // A 404 is fatal and never retried.
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/main/missing.json"))
+        .respond_with(ResponseTemplate::new(404))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let mut cmd = Command::cargo_bin("possum").unwrap();
+    let output = cmd
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "download",
+            "--repository",
+            "test/model",
+            "--to",
+            temp_dir.path().to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(10));
+
+    let model_dir = temp_dir.path().join("test").join("model");
+    assert!(model_dir.join("config.json").exists());
+    assert!(!model_dir.join("missing.json").exists());
+}
+
+#[tokio::test]
+async fn test_model_download_in_segments() {
+    let mock_server = MockServer::start().await;
+
+    let body = "0123456789abcdefghijklmnopqrstuvwxyz";
+    let sha256 = "74e7e5bb9d22d6db26bf76946d40fff3ea9f0346b884fd0694920fccfad15e33";
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "siblings": [{
+                "rfilename": "model.gguf",
+                "size": body.len(),
+                "lfs": {"sha256": sha256, "size": body.len()}
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    // Serve whatever byte range is asked for.
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/main/model.gguf"))
+        .respond_with(move |request: &wiremock::Request| {
+            let range = request.headers["range"].to_str().unwrap();
+            let (start, end) = range
+                .strip_prefix("bytes=")
+                .and_then(|r| r.split_once('-'))
+                .unwrap();
+            let (start, end): (usize, usize) = (start.parse().unwrap(), end.parse().unwrap());
+            ResponseTemplate::new(206)
+                .insert_header(
+                    "content-range",
+                    format!("bytes {start}-{end}/{}", body.len()).as_str(),
+                )
+                .set_body_string(&body[start..=end])
+        })
+        .expect(4)
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let mut cmd = Command::cargo_bin("possum").unwrap();
+    let output = cmd
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "download",
+            "--repository",
+            "test/model",
+            "--to",
+            temp_dir.path().to_str().unwrap(),
+            "--segment-size",
+            "10",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+
+    let model_dir = temp_dir.path().join("test").join("model");
+    assert_eq!(
+        std::fs::read_to_string(model_dir.join("model.gguf")).unwrap(),
+        body
+    );
+    assert!(!model_dir.join("model.gguf.incomplete.json").exists());
+}
+
+#[tokio::test]
+async fn test_model_metadata_not_found_exit_code() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/nobody/nothing"))
+        .respond_with(ResponseTemplate::new(401).insert_header("x-error-code", "RepoNotFound"))
+        .mount(&mock_server)
+        .await;
+
+    let mut cmd = Command::cargo_bin("possum").unwrap();
+    let output = cmd
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "metadata",
+            "--repository",
+            "nobody/nothing",
+        ])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("not found"));
+}
+
+#[tokio::test]
+async fn test_model_revisions_sends_token() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/our-org/private-model/refs"))
+        .and(header("authorization", "Bearer hf_secret"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({"branches": [{"name": "main"}]})),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let mut cmd = Command::cargo_bin("possum").unwrap();
+    let output = cmd
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "--token",
+            "hf_secret",
+            "model",
+            "revisions",
+            "--repository",
+            "our-org/private-model",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("main"));
+}
+
+#[tokio::test]
+async fn test_auth_login_whoami_logout() {
+    let mock_server = MockServer::start().await;
+    let temp_dir = tempfile::tempdir().unwrap();
+    let token_path = temp_dir.path().join("hf").join("token");
+
+    Mock::given(method("GET"))
+        .and(path("/api/whoami-v2"))
+        .and(header("authorization", "Bearer hf_secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "type": "user",
+            "name": "possum",
+            "orgs": [{"name": "our-org"}]
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/whoami-v2"))
+        .respond_with(ResponseTemplate::new(401))
+        .mount(&mock_server)
+        .await;
+
+    let possum = |args: &[&str], stdin: &str| {
+        Command::cargo_bin("possum")
+            .unwrap()
+            .env("HF_TOKEN_PATH", &token_path)
+            .env_remove("HF_TOKEN")
+            .env_remove("HUGGING_FACE_HUB_TOKEN")
+            .args(["--api-base-url", &mock_server.uri(), "auth"])
+            .args(args)
+            .write_stdin(stdin)
+            .output()
+            .unwrap()
+    };
+
+    // An invalid token is rejected and not stored.
+    let output = possum(&["login"], "hf_wrong\n");
+    assert_eq!(output.status.code(), Some(4));
+    assert!(!token_path.exists());
+
+    // A valid token read from stdin is stored...
+    let output = possum(&["login"], "hf_secret\n");
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(&token_path).unwrap(), "hf_secret");
+
+    // ...and picked up by later commands.
+    let output = possum(&["whoami"], "");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("possum"));
+    assert!(stdout.contains("orgs: our-org"));
+
+    let output = possum(&["logout"], "");
+    assert!(output.status.success());
+    assert!(!token_path.exists());
+    assert_eq!(possum(&["whoami"], "").status.code(), Some(1));
+}
+
+#[tokio::test]
+async fn test_model_tree_follows_pages() {
+    let mock_server = MockServer::start().await;
+    let next = format!(
+        "<{}/api/models/test/model/tree/main?recursive=true&cursor=page2>; rel=\"next\"",
+        mock_server.uri()
+    );
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model/tree/main"))
+        .and(query_param("cursor", "page2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {"type": "file", "oid": "b2", "size": 2048, "path": "weights/model.safetensors",
+             "lfs": {"oid": "aa", "size": 2048, "pointerSize": 130}}
+        ])))
+        .with_priority(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model/tree/main"))
+        .and(query_param("recursive", "true"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("link", next.as_str())
+                .set_body_json(json!([
+                    {"type": "file", "oid": "b1", "size": 665, "path": "config.json"},
+                    {"type": "directory", "oid": "t1", "size": 0, "path": "weights"}
+                ])),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let mut cmd = Command::cargo_bin("possum").unwrap();
+    let output = cmd
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "tree",
+            "--repository",
+            "test/model",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("config.json  665 B"));
+    assert!(stdout.contains("└── weights/  2.0 KiB"));
+    assert!(stdout.contains("model.safetensors  2.0 KiB"));
+    assert!(stdout.contains("2 file(s)"));
+}
+
+#[tokio::test]
+async fn test_model_download_checks_free_space() {
+    let mock_server = MockServer::start().await;
+
+    // A petabyte of weights won't fit in the test's temporary directory.
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "siblings": [
+                {"rfilename": "model.safetensors", "size": 1_000_000_000_000_000u64}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/main/model.safetensors"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("fake model data"))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let mut cmd = Command::cargo_bin("possum").unwrap();
+    let output = cmd
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "download",
+            "--repository",
+            "test/model",
+            "--to",
+            temp_dir.path().to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(11));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("not enough disk space"));
+}
+
+#[tokio::test]
+async fn test_model_du() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model/tree/main"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {"type": "file", "oid": "a", "size": 665, "path": "config.json"},
+            {"type": "file", "oid": "b", "size": 2048, "path": "model.safetensors"},
+            {"type": "file", "oid": "c", "size": 4096, "path": "pytorch_model.bin"},
+            {"type": "directory", "oid": "d", "size": 0, "path": "figures"},
+            {"type": "file", "oid": "e", "size": 1024, "path": "figures/plot.png"}
+        ])))
+        .mount(&mock_server)
+        .await;
+
+    let mut cmd = Command::cargo_bin("possum").unwrap();
+    let output = cmd
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "du",
+            "--repository",
+            "test/model",
+            "--exclude",
+            "figures/*",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("665 B  config.json"));
+    assert!(stdout.contains("2.0 KiB  model.safetensors"));
+    // Skipped in favour of the safetensors weights, as a download would.
+    assert!(!stdout.contains("pytorch_model.bin"));
+    assert!(!stdout.contains("plot.png"));
+    assert!(stdout.contains("2.6 KiB  total (2 file(s))"));
+}
+
+#[tokio::test]
+async fn test_model_download_dry_run() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "abc123",
+            "siblings": [
+                {"rfilename": "config.json", "size": 2},
+                {"rfilename": "model.safetensors", "size": 15},
+                {"rfilename": "pytorch_model.bin", "size": 20},
+                {"rfilename": "tokenizer.json", "size": 30}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/test/model/resolve/"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let model_dir = temp_dir.path().join("test").join("model");
+    std::fs::create_dir_all(&model_dir).unwrap();
+    std::fs::write(model_dir.join("config.json"), "{}").unwrap();
+    std::fs::write(model_dir.join("model.safetensors.incomplete"), "fake ").unwrap();
+
+    let dry_run = |extra: &[&str]| {
+        Command::cargo_bin("possum")
+            .unwrap()
+            .args([
+                "--api-base-url",
+                &mock_server.uri(),
+                "model",
+                "download",
+                "--repository",
+                "test/model",
+                "--to",
+                temp_dir.path().to_str().unwrap(),
+                "--dry-run",
+            ])
+            .args(extra)
+            .output()
+            .unwrap()
+    };
+
+    let output = dry_run(&[]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("test/model @ main (commit abc123)"));
+    assert!(stdout.contains("up-to-date          2 B  config.json"));
+    assert!(stdout.contains("resume             15 B  model.safetensors"));
+    assert!(stdout.contains("fetch              30 B  tokenizer.json"));
+    assert!(stdout.contains("skip                  -  pytorch_model.bin"));
+    assert!(stdout.contains("3 file(s) selected, 40 B to fetch"));
+
+    let output = dry_run(&["--json"]);
+    assert!(output.status.success());
+    let plan: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(plan["commit"], "abc123");
+    assert_eq!(
+        plan["files"][1]["local"],
+        json!({"state": "partial", "bytes": 5})
+    );
+    assert_eq!(plan["skipped"], json!(["pytorch_model.bin"]));
+    assert!(!model_dir.join("tokenizer.json").exists());
+}
+
+#[tokio::test]
+async fn test_model_download_skips_up_to_date_files() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "abc123",
+            "siblings": [
+                // `printf '{}' | git hash-object --stdin`
+                {"rfilename": "config.json", "size": 2,
+                 "blobId": "9e26dfeeb6e641a33dae4961196235bdb965b21b"}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/abc123/config.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let download = |extra: &[&str]| {
+        Command::cargo_bin("possum")
+            .unwrap()
+            .args([
+                "--api-base-url",
+                &mock_server.uri(),
+                "model",
+                "download",
+                "--repository",
+                "test/model",
+                "--to",
+                temp_dir.path().to_str().unwrap(),
+            ])
+            .args(extra)
+            .assert()
+            .success();
+    };
+
+    // The second run finds the file in place and fetches nothing; `--force`
+    // fetches it again.
+    download(&[]);
+    let model_dir = temp_dir.path().join("test").join("model");
+    assert!(model_dir.join(".possum.json").exists());
+    download(&[]);
+    download(&["--force"]);
+    assert_eq!(
+        std::fs::read_to_string(model_dir.join("config.json")).unwrap(),
+        "{}"
+    );
+}
+
+#[tokio::test]
+async fn test_model_download_pins_commit() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model/revision/dev"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "abc123",
+            "siblings": [
+                {"rfilename": "a.json"},
+                {"rfilename": "b.json"}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+    // Both files come from the resolved commit, never from the branch.
+    Mock::given(method("GET"))
+        .and(path_regex("^/test/model/resolve/abc123/[ab]\\.json$"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/test/model/resolve/dev/"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    Command::cargo_bin("possum")
+        .unwrap()
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "download",
+            "--repository",
+            "test/model",
+            "--revision",
+            "dev",
+            "--to",
+            temp_dir.path().to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let sidecar = temp_dir
+        .path()
+        .join("test")
+        .join("model:dev")
+        .join(".possum.json");
+    let sidecar: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(sidecar).unwrap()).unwrap();
+    assert_eq!(sidecar["repository"], "test/model");
+    assert_eq!(sidecar["revision"], "dev");
+    assert_eq!(sidecar["commit"], "abc123");
+}
+
+#[tokio::test]
+async fn test_model_download_records_commit_only_when_complete() {
+    let mock_server = MockServer::start().await;
+
+    let info = |sha: &str, files: &[&str]| {
+        let siblings: Vec<_> = files.iter().map(|f| json!({"rfilename": f})).collect();
+        json!({"sha": sha, "siblings": siblings})
+    };
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(info("abc123", &["a.json"])))
+        .up_to_n_times(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(info("def456", &["a.json", "b.json"])),
+        )
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/test/model/resolve/[a-z0-9]+/a\\.json$"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/def456/b.json"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let download = || {
+        Command::cargo_bin("possum")
+            .unwrap()
+            .args([
+                "--api-base-url",
+                &mock_server.uri(),
+                "model",
+                "download",
+                "--repository",
+                "test/model",
+                "--to",
+                temp_dir.path().to_str().unwrap(),
+            ])
+            .assert()
+    };
+    let sidecar = || {
+        let path = temp_dir
+            .path()
+            .join("test")
+            .join("model")
+            .join(".possum.json");
+        serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(path).unwrap()).unwrap()
+    };
+
+    download().success();
+    assert_eq!(sidecar()["commit"], "abc123");
+
+    // `b.json` of the new commit fails, so the directory still holds the
+    // old commit as far as the sidecar is concerned.
+    download().code(10);
+    let sidecar = sidecar();
+    assert_eq!(sidecar["commit"], "abc123");
+    assert!(sidecar["files"]["a.json"].is_object());
+    assert!(sidecar["listing"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|sibling| sibling["rfilename"] != "b.json"));
+}
+
+#[tokio::test]
+async fn test_model_lock_and_locked_download() {
+    let mock_server = MockServer::start().await;
+
+    let info = |config_blob_id: &str| {
+        json!({
+            "sha": "abc123",
+            "siblings": [
+                {"rfilename": "config.json", "size": 2, "blobId": config_blob_id},
+                {"rfilename": "README.md", "size": 5, "blobId": "0".repeat(40)}
+            ]
+        })
+    };
+    // `printf '{}' | git hash-object --stdin`
+    let config_blob_id = "9e26dfeeb6e641a33dae4961196235bdb965b21b";
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(info(config_blob_id)))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model/revision/abc123"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(info(config_blob_id)))
+        .up_to_n_times(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/abc123/config.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let lockfile = temp_dir.path().join("possum.lock");
+    let possum = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("possum").unwrap();
+        cmd.args(["--api-base-url", &mock_server.uri(), "model"])
+            .args(args);
+        cmd
+    };
+
+    possum(&[
+        "lock",
+        "--repository",
+        "test/model",
+        "--include",
+        "*.json",
+        "--output",
+        lockfile.to_str().unwrap(),
+    ])
+    .assert()
+    .success();
+    let lock = std::fs::read_to_string(&lockfile).unwrap();
+    assert!(lock.contains("commit = \"abc123\""));
+    assert!(lock.contains("path = \"config.json\""));
+    assert!(lock.contains(&format!("blob_id = \"{config_blob_id}\"")));
+    assert!(!lock.contains("README.md"));
+
+    let to = temp_dir.path().join("models");
+    let locked_download = || {
+        possum(&[
+            "download",
+            "--locked",
+            lockfile.to_str().unwrap(),
+            "--to",
+            to.to_str().unwrap(),
+        ])
+        .assert()
+    };
+    locked_download().success();
+    assert_eq!(
+        std::fs::read_to_string(to.join("test").join("model").join("config.json")).unwrap(),
+        "{}"
+    );
+
+    // The hub now publishes a different digest for the locked commit.
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model/revision/abc123"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(info(&"1".repeat(40))))
+        .mount(&mock_server)
+        .await;
+    locked_download().code(8);
+}
+
+#[tokio::test]
+async fn test_locked_download_into_hf_cache_keeps_refs() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model/revision/abc123"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "abc123",
+            "siblings": [
+                {"rfilename": "config.json", "size": 2, "blobId": "9e26dfeeb6e641a33dae4961196235bdb965b21b"}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/abc123/config.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let lockfile = temp_dir.path().join("possum.lock");
+    std::fs::write(
+        &lockfile,
+        r#"version = 1
+repository = "test/model"
+revision = "main"
+commit = "abc123"
+
+[[files]]
+path = "config.json"
+size = 2
+blob_id = "9e26dfeeb6e641a33dae4961196235bdb965b21b"
+"#,
+    )
+    .unwrap();
+    // `main` has since moved on, and the cache already knows it.
+    let cache_dir = temp_dir.path().join("cache");
+    let repo_dir = cache_dir.join("models--test--model");
+    std::fs::create_dir_all(repo_dir.join("refs")).unwrap();
+    std::fs::write(repo_dir.join("refs").join("main"), "def456").unwrap();
+
+    let mut cmd = Command::cargo_bin("possum").unwrap();
+    let output = cmd
+        .env("HF_HUB_CACHE", &cache_dir)
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "download",
+            "--locked",
+            lockfile.to_str().unwrap(),
+            "--hf-cache",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    assert_eq!(
+        std::fs::read_to_string(repo_dir.join("refs").join("main")).unwrap(),
+        "def456"
+    );
+    let snapshot = repo_dir
+        .join("snapshots")
+        .join("abc123")
+        .join("config.json");
+    assert_eq!(std::fs::read_to_string(snapshot).unwrap(), "{}");
+}
+
+#[tokio::test]
+async fn test_locked_download_checks_lock_when_hub_omits_digests() {
+    let mock_server = MockServer::start().await;
+
+    // The hub lists the files but publishes neither sizes nor digests, so
+    // only the lock can catch the tampered contents.
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model/revision/abc123"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "abc123",
+            "siblings": [{"rfilename": "a.bin"}, {"rfilename": "b.bin"}]
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/abc123/a.bin"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("TAMPERED"))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/abc123/b.bin"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("jello"))
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let lockfile = temp_dir.path().join("possum.lock");
+    // `printf hello | sha256sum`
+    let hello = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+    std::fs::write(
+        &lockfile,
+        format!(
+            r#"version = 1
+repository = "test/model"
+revision = "main"
+commit = "abc123"
+
+[[files]]
+path = "a.bin"
+size = 5
+sha256 = "{hello}"
+
+[[files]]
+path = "b.bin"
+size = 5
+sha256 = "{hello}"
+"#
+        ),
+    )
+    .unwrap();
+
+    let output = Command::cargo_bin("possum")
+        .unwrap()
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "download",
+            "--locked",
+            lockfile.to_str().unwrap(),
+            "--to",
+            temp_dir.path().to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(10));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("a.bin: size mismatch (expected 5, got 8)"));
+    assert!(stderr.contains("b.bin: sha256 mismatch"));
+
+    let model = temp_dir.path().join("test").join("model");
+    assert!(!model.join("a.bin").exists());
+    assert!(!model.join("b.bin").exists());
+}
+
+#[tokio::test]
+async fn test_sync_manifest() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "abc123",
+            "siblings": [
+                {"rfilename": "config.json", "size": 2},
+                {"rfilename": "README.md", "size": 5}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/other/revision/v1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "def456",
+            "siblings": [{"rfilename": "config.json", "size": 2}]
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex(
+            "^/test/(model|other)/resolve/[0-9a-f]+/config.json$",
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path().join("models");
+    let other = temp_dir.path().join("other");
+    let manifest = temp_dir.path().join("models.toml");
+    let write_manifest = |extra: &str| {
+        std::fs::write(
+            &manifest,
+            format!(
+                r#"
+to = "{}"
+
+[[models]]
+repository = "test/model"
+include = ["*.json"]
+
+[[models]]
+repository = "test/other"
+revision = "v1"
+to = "{}"
+{extra}"#,
+                root.display(),
+                other.display()
+            ),
+        )
+        .unwrap();
+    };
+    let sync = || {
+        Command::cargo_bin("possum")
+            .unwrap()
+            .args(["--api-base-url", &mock_server.uri(), "sync"])
+            .arg(&manifest)
+            .args(["--concurrency", "1"])
+            .output()
+            .unwrap()
+    };
+
+    write_manifest("");
+    let output = sync();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("ok      test/model  1 file(s)"));
+    assert!(stdout.contains("2 of 2 model(s) synced"));
+    assert!(root.join("test").join("model").join("config.json").exists());
+    assert!(!root.join("test").join("model").join("README.md").exists());
+    assert!(other.join("config.json").exists());
+
+    // One missing model fails the sync, but not the others.
+    write_manifest("\n[[models]]\nrepository = \"test/missing\"\n");
+    let output = sync();
+    assert_eq!(output.status.code(), Some(10));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("FAILED  test/missing  not found"));
+    assert!(stdout.contains("2 of 3 model(s) synced"));
+}
+
+#[tokio::test]
+async fn test_model_download_prune() {
+    let mock_server = MockServer::start().await;
+
+    // The first download sees an old shard, which later runs no longer do.
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "abc123",
+            "siblings": [
+                {"rfilename": "config.json", "size": 2},
+                {"rfilename": "shards/old.safetensors", "size": 2}
+            ]
+        })))
+        .up_to_n_times(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "def456",
+            "siblings": [
+                {"rfilename": "config.json", "size": 2},
+                {"rfilename": "new.safetensors", "size": 2}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/test/model/resolve/"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let download = |extra: &[&str]| {
+        Command::cargo_bin("possum")
+            .unwrap()
+            .args([
+                "--api-base-url",
+                &mock_server.uri(),
+                "model",
+                "download",
+                "--repository",
+                "test/model",
+                "--to",
+                temp_dir.path().to_str().unwrap(),
+            ])
+            .args(extra)
+            .output()
+            .unwrap()
+    };
+
+    assert!(download(&[]).status.success());
+    let model_dir = temp_dir.path().join("test").join("model");
+    let old = model_dir.join("shards").join("old.safetensors");
+    assert!(old.exists());
+    // Not downloaded by possum, so never pruned.
+    std::fs::write(model_dir.join("notes.txt"), "mine").unwrap();
+
+    let output = download(&["--prune", "--dry-run"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("delete                -  shards/old.safetensors"));
+    assert!(stdout.contains("2 file(s) selected, 2 B to fetch, 1 file(s) to delete"));
+    assert!(old.exists());
+
+    assert!(download(&["--prune"]).status.success());
+    assert!(!old.exists());
+    assert!(!model_dir.join("shards").exists());
+    assert!(model_dir.join("new.safetensors").exists());
+    assert!(model_dir.join("notes.txt").exists());
+    let sidecar = std::fs::read_to_string(model_dir.join(".possum.json")).unwrap();
+    assert!(!sidecar.contains("old.safetensors"));
+}
+
+#[tokio::test]
+async fn test_model_download_report() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "abc123",
+            "siblings": [
+                // `printf '{}' | git hash-object --stdin`
+                {"rfilename": "config.json", "size": 2,
+                 "blobId": "9e26dfeeb6e641a33dae4961196235bdb965b21b"},
+                {"rfilename": "missing.json", "size": 2}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/abc123/config.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/abc123/missing.json"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let report_path = temp_dir.path().join("report.json");
+    let output = Command::cargo_bin("possum")
+        .unwrap()
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "download",
+            "--repository",
+            "test/model",
+            "--to",
+            temp_dir.path().to_str().unwrap(),
+            "--report",
+            report_path.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    // The report is printed and written even though a file failed.
+    assert_eq!(output.status.code(), Some(10));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("STATUS"));
+    assert!(stdout.contains("downloaded          2 B"));
+    assert!(stdout.contains("1 downloaded, 0 skipped, 1 failed; 2 B in"));
+    assert!(stdout.contains("from commit abc123"));
+
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report_path).unwrap()).unwrap();
+    assert_eq!(report["commit"], "abc123");
+    assert_eq!(report["bytes"], 2);
+    assert_eq!(report["files"][0]["file"], "config.json");
+    assert_eq!(report["files"][0]["outcome"], "downloaded");
+    assert_eq!(report["files"][0]["verification"], "git sha1 verified");
+    assert_eq!(report["files"][1]["outcome"], "failed");
+    assert!(report["files"][1]["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("not found"));
+    assert!(report["duration_secs"].is_f64());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_model_download_cancelled_by_ctrl_c() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "abc123",
+            "siblings": [
+                {"rfilename": "slow.bin", "size": 2},
+                {"rfilename": "queued.bin", "size": 2}
+            ]
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/test/model/resolve/abc123/"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string("{}")
+                .set_delay(std::time::Duration::from_secs(60)),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let child = std::process::Command::new(assert_cmd::cargo::cargo_bin("possum"))
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "download",
+            "--repository",
+            "test/model",
+            "--to",
+            temp_dir.path().to_str().unwrap(),
+            "--concurrency",
+            "1",
+        ])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Interrupt once the first file is in flight.
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(30);
+    loop {
+        let requests = mock_server.received_requests().await.unwrap_or_default();
+        if requests.iter().any(|r| r.url.path().contains("/resolve/")) {
+            break;
+        }
+        assert!(std::time::Instant::now() < deadline, "no file requested");
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    let status = std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("0 downloaded, 0 skipped, 0 failed, 2 cancelled"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("left unfinished"));
+    assert!(stderr.contains("  slow.bin"));
+    assert!(stderr.contains("  queued.bin"));
+    let model_dir = temp_dir.path().join("test").join("model");
+    assert!(!model_dir.join("slow.bin").exists());
+    assert!(!model_dir.join("queued.bin").exists());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_model_download_cancelled_mid_body_keeps_partial() {
+    let mock_server = MockServer::start().await;
+
+    // A server that sends the headers and the first 4 of 10 bytes, then
+    // stalls with the connection open.
+    let stalling = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let stalling_addr = stalling.local_addr().unwrap();
+    tokio::spawn(async move {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let (mut socket, _) = stalling.accept().await.unwrap();
+        let mut request = vec![];
+        let mut buf = [0; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = socket.read(&mut buf).await.unwrap();
+            assert!(n > 0, "connection closed mid-request");
+            request.extend_from_slice(&buf[..n]);
+        }
+        socket
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nabcd")
+            .await
+            .unwrap();
+        socket.flush().await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        drop(socket);
+    });
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "abc123",
+            "siblings": [{"rfilename": "slow.bin", "size": 10}]
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/abc123/slow.bin"))
+        .respond_with(
+            ResponseTemplate::new(302)
+                .insert_header("location", format!("http://{stalling_addr}/slow.bin")),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let child = std::process::Command::new(assert_cmd::cargo::cargo_bin("possum"))
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "download",
+            "--repository",
+            "test/model",
+            "--to",
+            temp_dir.path().to_str().unwrap(),
+        ])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Interrupt once the first bytes of the body are on disk.
+    let model_dir = temp_dir.path().join("test").join("model");
+    let incomplete = model_dir.join("slow.bin.incomplete");
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(30);
+    while std::fs::metadata(&incomplete).map_or(0, |m| m.len()) < 4 {
+        assert!(std::time::Instant::now() < deadline, "no bytes written");
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    let status = std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("0 downloaded, 0 skipped, 0 failed, 1 cancelled"));
+    // The partial body is kept for the next run to resume from.
+    assert_eq!(std::fs::read_to_string(&incomplete).unwrap(), "abcd");
+    assert!(!model_dir.join("slow.bin").exists());
+}
+
+#[tokio::test]
+async fn test_model_download_max_bandwidth() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "abc123",
+            "siblings": [{"rfilename": "weights.bin", "size": 4000}]
+        })))
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/abc123/weights.bin"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![7u8; 4000]))
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let started = std::time::Instant::now();
+    Command::cargo_bin("possum")
+        .unwrap()
+        .args([
+            "--api-base-url",
+            &mock_server.uri(),
+            "model",
+            "download",
+            "--repository",
+            "test/model",
+            "--to",
+            temp_dir.path().to_str().unwrap(),
+            "--max-bandwidth",
+            "2KB/s",
+        ])
+        .assert()
+        .success();
+
+    // A second's worth comes at once; the other 2 KB take another second.
+    assert!(started.elapsed() >= std::time::Duration::from_millis(800));
+    let downloaded = temp_dir.path().join("test/model/weights.bin");
+    assert_eq!(std::fs::read(downloaded).unwrap().len(), 4000);
+}
+
+#[tokio::test]
+async fn test_requests_go_through_proxy() {
+    // The mock server stands in for the proxy: requests for an unreachable
+    // hub only succeed if they're sent to it.
+    let proxy = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "modelId": "test/model",
+            "sha": "abc123"
+        })))
+        .expect(1)
+        .mount(&proxy)
+        .await;
+
+    let output = Command::cargo_bin("possum")
+        .unwrap()
+        .env_remove("NO_PROXY")
+        .env_remove("no_proxy")
+        .args([
+            "--api-base-url",
+            "http://hub.invalid",
+            "--proxy",
+            &proxy.uri(),
+            "model",
+            "metadata",
+            "--repository",
+            "test/model",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("abc123"));
+}
+
+#[test]
+fn test_bad_ca_bundle_exit_code() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let bundle = temp_dir.path().join("ca.pem");
+    std::fs::write(&bundle, "not a certificate\n").unwrap();
+
+    let output = Command::cargo_bin("possum")
+        .unwrap()
+        .args([
+            "--ca-bundle",
+            bundle.to_str().unwrap(),
+            "model",
+            "metadata",
+            "--repository",
+            "test/model",
+        ])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("CA bundle"));
+}
+
+#[tokio::test]
+async fn test_model_download_falls_back_to_mirror() {
+    let primary = MockServer::start().await;
+    let mirror = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&primary)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "abc123",
+            // `printf '{}' | git hash-object --stdin`
+            "siblings": [{"rfilename": "config.json", "size": 2,
+                          "blobId": "9e26dfeeb6e641a33dae4961196235bdb965b21b"}]
+        })))
+        .mount(&mirror)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/abc123/config.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .mount(&mirror)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let report_path = temp_dir.path().join("report.json");
+    let output = Command::cargo_bin("possum")
+        .unwrap()
+        .args([
+            "--api-base-url",
+            &format!("{},{}", primary.uri(), mirror.uri()),
+            "model",
+            "download",
+            "--repository",
+            "test/model",
+            "--to",
+            temp_dir.path().to_str().unwrap(),
+            "--max-attempts",
+            "1",
+            "--report",
+            report_path.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("from commit abc123 at {}", mirror.uri())));
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report_path).unwrap()).unwrap();
+    assert_eq!(report["endpoints"], json!([mirror.uri()]));
+    assert_eq!(report["files"][0]["endpoint"], mirror.uri());
+    assert!(temp_dir.path().join("test/model/config.json").exists());
+    // Once the mirror answered, the file came straight from it.
+    assert_eq!(primary.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_model_download_reports_each_files_endpoint() {
+    let primary = MockServer::start().await;
+    let mirror = MockServer::start().await;
+
+    // The primary lists the files and serves `a.json`, but fails `b.json`,
+    // which the mirror serves instead.
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "abc123",
+            "siblings": [{"rfilename": "a.json"}, {"rfilename": "b.json"}]
+        })))
+        .mount(&primary)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/abc123/a.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .mount(&primary)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/abc123/b.json"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&primary)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/abc123/b.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .mount(&mirror)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let report_path = temp_dir.path().join("report.json");
+    let output = Command::cargo_bin("possum")
+        .unwrap()
+        .args([
+            "--api-base-url",
+            &format!("{},{}", primary.uri(), mirror.uri()),
+            "model",
+            "download",
+            "--repository",
+            "test/model",
+            "--to",
+            temp_dir.path().to_str().unwrap(),
+            "--concurrency",
+            "1",
+            "--max-attempts",
+            "1",
+            "--report",
+            report_path.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!(
+        "from commit abc123 at {}, {}",
+        primary.uri(),
+        mirror.uri()
+    )));
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report_path).unwrap()).unwrap();
+    assert_eq!(report["endpoints"], json!([primary.uri(), mirror.uri()]));
+    assert_eq!(report["files"][0]["endpoint"], primary.uri());
+    assert_eq!(report["files"][1]["endpoint"], mirror.uri());
+}
+
+#[tokio::test]
+async fn test_hf_endpoint_with_unreachable_first_endpoint() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "modelId": "test/model",
+            "sha": "abc123"
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    // Nothing listens on port 1, so the request falls back to the mock.
+    let output = Command::cargo_bin("possum")
+        .unwrap()
+        .env(
+            "HF_ENDPOINT",
+            format!("http://127.0.0.1:1,{}", mock_server.uri()),
+        )
+        .args(["model", "metadata", "--repository", "test/model"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("abc123"));
+}
+
+#[tokio::test]
+async fn test_model_download_offline() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/models/test/model"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "abc123",
+            "siblings": [
+                // `printf '{}' | git hash-object --stdin`
+                {"rfilename": "config.json", "size": 2,
+                 "blobId": "9e26dfeeb6e641a33dae4961196235bdb965b21b"},
+                {"rfilename": "weights.bin", "size": 4}
+            ]
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/test/model/resolve/abc123/config.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let download = |endpoint: &str, offline: bool, extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("possum").unwrap();
+        if offline {
+            cmd.env("HF_HUB_OFFLINE", "1");
+        }
+        cmd.args([
+            "--api-base-url",
+            endpoint,
+            "model",
+            "download",
+            "--repository",
+            "test/model",
+            "--to",
+            temp_dir.path().to_str().unwrap(),
+        ])
+        .args(extra)
+        .output()
+        .unwrap()
+    };
+
+    // Only config.json is fetched, but the whole listing is recorded.
+    let output = download(&mock_server.uri(), false, &["--include", "config.json"]);
+    assert!(output.status.success());
+
+    // Offline, nothing reaches the hub (nothing listens on port 1): the
+    // downloaded selection is found on disk...
+    let output = download("http://127.0.0.1:1", true, &["--include", "config.json"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("0 downloaded, 1 skipped, 0 failed"));
+
+    // ...and a selection that isn't fails with the missing files listed.
+    let output = download("http://127.0.0.1:1", true, &[]);
+    assert_eq!(output.status.code(), Some(13));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("test/model @ main is not available offline (1 file(s) missing)"));
+    assert!(stderr.contains("missing: weights.bin"));
+    assert!(!stderr.contains("missing: config.json"));
+
+    // Commands that need the hub refuse to run.
+    for command in ["revisions", "metadata"] {
+        let output = Command::cargo_bin("possum")
+            .unwrap()
+            .args(["--offline", "model", command, "--repository", "test/model"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("needs the hub"));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_model_list_files_offline() {
+    // A hub cache holding one commit of the repository, as a download with
+    // `--hf-cache` leaves it; no hub is running at all.
+    let cache_dir = tempfile::tempdir().unwrap();
+    let repo_dir = cache_dir.path().join("models--test--model");
+    let blob_id = "9e26dfeeb6e641a33dae4961196235bdb965b21b";
+    std::fs::create_dir_all(repo_dir.join("blobs")).unwrap();
+    std::fs::write(repo_dir.join("blobs").join(blob_id), "{}").unwrap();
+    std::fs::create_dir_all(repo_dir.join("refs")).unwrap();
+    std::fs::write(repo_dir.join("refs").join("main"), "abc123").unwrap();
+    let snapshot = repo_dir.join("snapshots").join("abc123");
+    std::fs::create_dir_all(snapshot.join("onnx")).unwrap();
+    for file in ["config.json", "onnx/config.json"] {
+        let depth = file.matches('/').count();
+        let blob = format!("{}../../blobs/{blob_id}", "../".repeat(depth));
+        std::os::unix::fs::symlink(blob, snapshot.join(file)).unwrap();
+    }
+
+    let possum = |args: &[&str]| {
+        Command::cargo_bin("possum")
+            .unwrap()
+            .env("HF_HUB_OFFLINE", "1")
+            .env("HF_HUB_CACHE", cache_dir.path())
+            .args(["--api-base-url", "http://127.0.0.1:1", "model"])
+            .args(args)
+            .args(["--repository", "test/model", "--hf-cache"])
+            .output()
+            .unwrap()
+    };
+
+    let output = possum(&["tree", "--json"]);
+    assert!(output.status.success());
+    let entries: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let paths: Vec<&str> = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(paths, ["config.json", "onnx/config.json", "onnx"]);
+    assert_eq!(entries[0]["size"], 2);
+    assert_eq!(entries[0]["oid"], blob_id);
+
+    let output = possum(&["du", "--include", "*.json"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("onnx/config.json"));
+    assert!(stdout.contains("total (2 file(s))"));
+
+    // A revision nothing was downloaded for is reported as such.
+    let output = possum(&["tree", "--revision", "v2"]);
+    assert_eq!(output.status.code(), Some(13));
+}
@@ -0,0 +1,163 @@
+//! Finding, storing and checking Hugging Face tokens, compatibly with
+//! `huggingface_hub` (and `huggingface-cli login`).
+
+use crate::model::cache::hf_home;
+use crate::{Error, HubClient, Result};
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The file a token is stored in: `$HF_TOKEN_PATH`, else `$HF_HOME/token`
+/// (see [`hf_home`]). `None` if no home directory can be found.
+pub fn token_path() -> Option<PathBuf> {
+    match env::var_os("HF_TOKEN_PATH") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(hf_home()?.join("token")),
+    }
+}
+
+/// The token to use: `explicit` (e.g. a `--token` flag), else `$HF_TOKEN`,
+/// else `$HUGGING_FACE_HUB_TOKEN`, else the contents of [`token_path`].
+/// Blank values are skipped.
+pub fn resolve_token(explicit: Option<&str>) -> Option<String> {
+    resolve_with(
+        explicit,
+        |name| env::var(name).ok(),
+        token_path().as_deref(),
+    )
+}
+
+fn resolve_with(
+    explicit: Option<&str>,
+    var: impl Fn(&str) -> Option<String>,
+    token_path: Option<&Path>,
+) -> Option<String> {
+    let from_file = || fs::read_to_string(token_path?).ok();
+    [
+        explicit.map(String::from),
+        var("HF_TOKEN"),
+        var("HUGGING_FACE_HUB_TOKEN"),
+    ]
+    .into_iter()
+    .flatten()
+    .chain(std::iter::once_with(from_file).flatten())
+    .map(|token| token.trim().to_string())
+    .find(|token| !token.is_empty())
+}
+
+/// Store `token` in [`token_path`] (readable only by the user on unix),
+/// returning the path.
+pub fn save_token(token: &str) -> Result<PathBuf> {
+    let path = token_path().ok_or_else(no_home)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, token.trim())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(path)
+}
+
+/// Remove the stored token, returning its path if there was one.
+pub fn delete_token() -> Result<Option<PathBuf>> {
+    let path = token_path().ok_or_else(no_home)?;
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(Some(path)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn no_home() -> Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        "cannot locate the token file; set HF_TOKEN_PATH or HF_HOME",
+    )
+    .into()
+}
+
+impl HubClient {
+    /// Who the client's token belongs to: the hub's `/api/whoami-v2`
+    /// response (`name`, `orgs`, ...). Fails with [`Error::Unauthorized`]
+    /// for a missing or invalid token.
+    pub async fn whoami(&self) -> Result<Value> {
+        let url = build_whoami_url(self.endpoint());
+        let response = self.send(&url).await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            Err(Error::from_response(&url, &response))
+        }
+    }
+}
+
+pub fn build_whoami_url(api_base_url: &str) -> String {
+    format!("{api_base_url}/api/whoami-v2")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_resolve_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("token");
+        fs::write(&file, "hf_file\n").unwrap();
+        let all = env(&[("HF_TOKEN", "hf_env"), ("HUGGING_FACE_HUB_TOKEN", "hf_old")]);
+
+        assert_eq!(
+            resolve_with(Some("hf_flag"), &all, Some(&file)),
+            Some("hf_flag".to_string())
+        );
+        assert_eq!(
+            resolve_with(None, &all, Some(&file)),
+            Some("hf_env".to_string())
+        );
+        assert_eq!(
+            resolve_with(
+                None,
+                env(&[("HUGGING_FACE_HUB_TOKEN", "hf_old")]),
+                Some(&file)
+            ),
+            Some("hf_old".to_string())
+        );
+        assert_eq!(
+            resolve_with(None, env(&[]), Some(&file)),
+            Some("hf_file".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_skips_blank_and_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("token");
+        assert_eq!(
+            resolve_with(None, env(&[("HF_TOKEN", " ")]), Some(&missing)),
+            None
+        );
+        assert_eq!(resolve_with(None, env(&[]), None), None);
+    }
+
+    #[test]
+    fn test_build_whoami_url() {
+        assert_eq!(
+            build_whoami_url("https://huggingface.co"),
+            "https://huggingface.co/api/whoami-v2"
+        );
+    }
+}
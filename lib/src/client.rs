@@ -0,0 +1,368 @@
+use crate::{Error, Result};
+use reqwest::{Certificate, Client, Identity, NoProxy, Proxy, RequestBuilder, Response};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The public Hugging Face hub.
+pub const DEFAULT_ENDPOINT: &str = "https://huggingface.co";
+
+/// A connection to the hub: one `reqwest` connection pool plus the
+/// endpoint, token and HTTP settings every request shares.
+///
+/// A client may have mirrors to fall back to (see
+/// [`HubClientBuilder::endpoints`]): a request that can't connect to an
+/// endpoint, or gets a `5xx` from it, is sent to the next, and the endpoint
+/// that answers is tried first from then on.
+///
+/// Its methods mirror the free functions in [`crate::model`] (which build a
+/// throwaway client per call); embedding services should build one
+/// `HubClient` and reuse it. Cloning is cheap and shares the pool.
+///
+/// ```no_run
+/// # async fn run() -> possum_lib::Result<()> {
+/// use possum_lib::HubClient;
+///
+/// let hub = HubClient::builder().token("hf_...").build()?;
+/// let branches = hub.revisions("TheBloke/Llama-2-7B-Chat-GPTQ").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct HubClient {
+    http: Client,
+    /// Never empty.
+    endpoints: Vec<String>,
+    /// The index of the endpoint that last answered, shared by clones.
+    active: Arc<AtomicUsize>,
+    token: Option<String>,
+}
+
+impl HubClient {
+    pub fn builder() -> HubClientBuilder {
+        HubClientBuilder::default()
+    }
+
+    /// A client with default settings for `endpoint`.
+    pub fn with_endpoint(endpoint: &str) -> Result<Self> {
+        Self::builder().endpoint(endpoint).build()
+    }
+
+    /// The hub's base URL (e.g. `https://huggingface.co`) that requests go
+    /// to first: the first of [`Self::endpoints`], unless it failed and a
+    /// mirror answered instead.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoints[self.active.load(Ordering::Relaxed)]
+    }
+
+    /// Every endpoint, in the order they're tried.
+    pub fn endpoints(&self) -> &[String] {
+        &self.endpoints
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// The same client (and connection pool), sending `token` instead.
+    pub fn with_token(&self, token: Option<&str>) -> Self {
+        Self {
+            token: token.map(String::from),
+            ..self.clone()
+        }
+    }
+
+    /// A GET request for `url`, authenticated with the client's token.
+    fn get(&self, url: &str) -> RequestBuilder {
+        let request = self.http.get(url);
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// GET `url` (see [`Self::send_with`]).
+    pub(crate) async fn send(&self, url: &str) -> Result<Response> {
+        self.send_with(url, |request| request).await
+    }
+
+    /// GET `url`, with `build` adding to the request. A `url` under one of
+    /// the client's endpoints goes to the active endpoint first, then to
+    /// each of the others in turn while they can't be reached or answer
+    /// `5xx`; the last one's outcome is returned.
+    pub(crate) async fn send_with(
+        &self,
+        url: &str,
+        build: impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> Result<Response> {
+        Ok(self.send_from(url, build).await?.0)
+    }
+
+    /// [`Self::send_with`], also returning the endpoint that answered (`None`
+    /// for a `url` under none of them).
+    pub(crate) async fn send_from(
+        &self,
+        url: &str,
+        build: impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> Result<(Response, Option<String>)> {
+        let Some(path) = self.endpoints.iter().find_map(|e| under(url, e)) else {
+            return Ok((build(self.get(url)).send().await?, None));
+        };
+        let active = self.active.load(Ordering::Relaxed);
+        let order: Vec<usize> = std::iter::once(active)
+            .chain((0..self.endpoints.len()).filter(|&i| i != active))
+            .collect();
+        for (n, &i) in order.iter().enumerate() {
+            let endpoint = &self.endpoints[i];
+            let last = n + 1 == order.len();
+            let result = build(self.get(&format!("{endpoint}{path}"))).send().await;
+            let failure = match &result {
+                Ok(response) if response.status().is_server_error() => {
+                    format!("HTTP {}", response.status())
+                }
+                Err(e) if e.is_connect() || e.is_timeout() => e.to_string(),
+                _ => {
+                    if i != active {
+                        tracing::info!("Using {endpoint} from now on");
+                        self.active.store(i, Ordering::Relaxed);
+                    }
+                    return Ok((result?, Some(endpoint.clone())));
+                }
+            };
+            if last {
+                return Ok((result?, Some(endpoint.clone())));
+            }
+            tracing::warn!(
+                "{endpoint} failed ({failure}); trying {}",
+                self.endpoints[order[n + 1]]
+            );
+        }
+        unreachable!("a client has at least one endpoint")
+    }
+}
+
+// The rest of `url` if it's under `endpoint` (its path and query).
+fn under<'a>(url: &'a str, endpoint: &str) -> Option<&'a str> {
+    url.strip_prefix(endpoint)
+        .filter(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+}
+
+/// Builds a [`HubClient`].
+#[derive(Debug, Clone)]
+pub struct HubClientBuilder {
+    endpoints: Vec<String>,
+    token: Option<String>,
+    user_agent: String,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    proxy: Option<String>,
+    ca_bundle: Option<PathBuf>,
+    client_cert: Option<(PathBuf, PathBuf)>,
+}
+
+impl Default for HubClientBuilder {
+    fn default() -> Self {
+        Self {
+            endpoints: vec![DEFAULT_ENDPOINT.to_string()],
+            token: None,
+            user_agent: concat!("possum/", env!("CARGO_PKG_VERSION")).to_string(),
+            connect_timeout: None,
+            read_timeout: None,
+            proxy: None,
+            ca_bundle: None,
+            client_cert: None,
+        }
+    }
+}
+
+impl HubClientBuilder {
+    /// The hub's base URL (default [`DEFAULT_ENDPOINT`]).
+    pub fn endpoint(self, endpoint: impl Into<String>) -> Self {
+        self.endpoints([endpoint])
+    }
+
+    /// The hub's base URLs in order of preference, e.g. an internal mirror
+    /// then the public hub: requests fall back to the next when one can't be
+    /// reached or answers `5xx`. An empty list keeps the current endpoints.
+    pub fn endpoints(mut self, endpoints: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let endpoints: Vec<String> = endpoints
+            .into_iter()
+            .map(|e| e.into().trim_end_matches('/').to_string())
+            .collect();
+        if !endpoints.is_empty() {
+            self.endpoints = endpoints;
+        }
+        self
+    }
+
+    /// The token sent as a bearer token with every request.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// The `User-Agent` header (default `possum/<version>`).
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// How long to wait for a connection to be established.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// How long a response may go without delivering any data. (There is
+    /// deliberately no overall timeout: a large file may take hours.)
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Send every request through the proxy at `url`, except to the hosts
+    /// `NO_PROXY` lists. Without one, `HTTPS_PROXY`/`HTTP_PROXY`/`ALL_PROXY`
+    /// (and `NO_PROXY`) are honoured as usual.
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// Trust the root certificates in the PEM file at `path` (e.g. a private
+    /// CA's), besides the system's.
+    pub fn ca_bundle(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ca_bundle = Some(path.into());
+        self
+    }
+
+    /// Present the certificate (chain) in the PEM file at `cert`, with its
+    /// PKCS#8 private key in the PEM file at `key`, to servers that ask for
+    /// one.
+    pub fn client_cert(mut self, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        self.client_cert = Some((cert.into(), key.into()));
+        self
+    }
+
+    pub fn build(self) -> Result<HubClient> {
+        let mut http = Client::builder().user_agent(self.user_agent);
+        if let Some(timeout) = self.connect_timeout {
+            http = http.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            http = http.read_timeout(timeout);
+        }
+        if let Some(proxy) = &self.proxy {
+            let proxy = Proxy::all(proxy)
+                .map_err(|e| Error::Config(format!("proxy {proxy}: {e}")))?
+                .no_proxy(NoProxy::from_env());
+            http = http.proxy(proxy);
+        }
+        if let Some(path) = &self.ca_bundle {
+            let certs = Certificate::from_pem_bundle(&read_pem(path)?)
+                .map_err(|e| Error::Config(format!("CA bundle {}: {e}", path.display())))?;
+            if certs.is_empty() {
+                return Err(Error::Config(format!(
+                    "CA bundle {}: no certificates found",
+                    path.display()
+                )));
+            }
+            for cert in certs {
+                http = http.add_root_certificate(cert);
+            }
+        }
+        if let Some((cert, key)) = &self.client_cert {
+            let identity =
+                Identity::from_pkcs8_pem(&read_pem(cert)?, &read_pem(key)?).map_err(|e| {
+                    Error::Config(format!("client certificate {}: {e}", cert.display()))
+                })?;
+            http = http.identity(identity);
+        }
+        Ok(HubClient {
+            http: http.build()?,
+            endpoints: self.endpoints,
+            active: Arc::new(AtomicUsize::new(0)),
+            token: self.token,
+        })
+    }
+}
+
+fn read_pem(path: &Path) -> Result<Vec<u8>> {
+    fs::read(path).map_err(|e| Error::Config(format!("{}: {e}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_defaults() {
+        let hub = HubClient::builder().build().unwrap();
+        assert_eq!(hub.endpoint(), DEFAULT_ENDPOINT);
+        assert_eq!(hub.token(), None);
+    }
+
+    #[test]
+    fn test_builder_endpoint_trims_trailing_slash() {
+        let hub = HubClient::with_endpoint("http://localhost:8080/").unwrap();
+        assert_eq!(hub.endpoint(), "http://localhost:8080");
+    }
+
+    #[test]
+    fn test_builder_endpoints() {
+        let hub = HubClient::builder()
+            .endpoints(["http://mirror.internal/hf/", DEFAULT_ENDPOINT])
+            .build()
+            .unwrap();
+        assert_eq!(hub.endpoint(), "http://mirror.internal/hf");
+        assert_eq!(hub.endpoints().len(), 2);
+        let empty: [&str; 0] = [];
+        let hub = HubClient::builder().endpoints(empty).build().unwrap();
+        assert_eq!(hub.endpoints(), [DEFAULT_ENDPOINT]);
+    }
+
+    #[test]
+    fn test_under() {
+        let endpoint = "http://localhost:8080";
+        assert_eq!(
+            under("http://localhost:8080/api/models", endpoint),
+            Some("/api/models")
+        );
+        assert_eq!(under("http://localhost:8080?x", endpoint), Some("?x"));
+        assert_eq!(under("http://localhost:80801/api", endpoint), None);
+        assert_eq!(under("https://cdn.example/a", endpoint), None);
+    }
+
+    #[test]
+    fn test_with_token() {
+        let hub = HubClient::builder().token("a").build().unwrap();
+        assert_eq!(hub.with_token(Some("b")).token(), Some("b"));
+        assert_eq!(hub.with_token(None).token(), None);
+        assert_eq!(hub.token(), Some("a"));
+    }
+
+    #[test]
+    fn test_builder_rejects_bad_proxy() {
+        assert!(matches!(
+            HubClient::builder().proxy("not a url").build(),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_builder_rejects_bad_ca_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.pem");
+        assert!(matches!(
+            HubClient::builder().ca_bundle(&missing).build(),
+            Err(Error::Config(_))
+        ));
+        let empty = dir.path().join("empty.pem");
+        fs::write(&empty, "not a certificate\n").unwrap();
+        assert!(matches!(
+            HubClient::builder().ca_bundle(&empty).build(),
+            Err(Error::Config(_))
+        ));
+    }
+}
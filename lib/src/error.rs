@@ -0,0 +1,263 @@
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Everything that can go wrong talking to the hub or writing files.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The repository, revision or file does not exist (or is private and
+    /// the token can't see it).
+    #[error("not found: {url}")]
+    NotFound { url: String },
+    /// The request needs a (valid) token.
+    #[error("unauthorized (a valid token is required): {url}")]
+    Unauthorized { url: String },
+    /// The repository is gated: its terms must be accepted on the hub, with
+    /// the token's account, before its files can be fetched.
+    #[error("gated repository (accept its terms on the hub and use a token): {url}")]
+    Gated { url: String },
+    /// The hub asked us to back off (`429`), possibly saying for how long.
+    #[error("rate limited: {url}")]
+    RateLimited {
+        url: String,
+        retry_after: Option<Duration>,
+    },
+    /// Any other non-success HTTP status.
+    #[error("HTTP {status}: {url}")]
+    Http { status: StatusCode, url: String },
+    /// The request never got a response: DNS, connect, TLS, timeouts, or a
+    /// connection dropped mid-body.
+    #[error("network error: {0}")]
+    Network(#[from] reqwest::Error),
+    /// A file system operation failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// A [`HubClientBuilder`](crate::HubClientBuilder) setting is unusable:
+    /// a malformed proxy URL, or a certificate or key that can't be read.
+    #[error("invalid client configuration: {0}")]
+    Config(String),
+    /// An include/exclude pattern is not a valid glob.
+    #[error("invalid glob: {0}")]
+    InvalidGlob(#[from] glob::PatternError),
+    /// A hub response was missing something we need, or malformed.
+    #[error("unexpected response: {0}")]
+    InvalidResponse(String),
+    /// A downloaded file's contents don't match the digest the hub
+    /// publishes for it.
+    #[error("{file}: {algorithm} mismatch (expected {expected}, got {actual})")]
+    Integrity {
+        file: String,
+        algorithm: &'static str,
+        expected: String,
+        actual: String,
+    },
+    /// The selected files won't fit in the free space of the filesystem
+    /// being downloaded to.
+    #[error(
+        "not enough disk space in {}: {needed} bytes needed, {available} available",
+        path.display()
+    )]
+    InsufficientSpace {
+        path: PathBuf,
+        needed: u64,
+        available: u64,
+    },
+    /// A lockfile can't be read, or the repository no longer has a file it
+    /// locks.
+    #[error("lockfile: {0}")]
+    Lock(String),
+    /// A manifest can't be read.
+    #[error("manifest: {0}")]
+    Manifest(String),
+    /// Some models of a [`HubClient::sync`](crate::HubClient::sync) failed.
+    #[error("{failed} of {total} model(s) failed to sync")]
+    SyncFailed { failed: usize, total: usize },
+    /// The download was stopped through its
+    /// [`DownloadRequest::cancel`](crate::model::DownloadRequest::cancel)
+    /// token.
+    #[error("cancelled")]
+    Cancelled,
+    /// Offline, the selected files aren't all on disk: `missing` lists
+    /// those that aren't, or is empty if nothing of the revision (or of the
+    /// selection) is known locally at all.
+    #[error(
+        "{repository} @ {revision} is not available offline{}",
+        missing_note(missing)
+    )]
+    NotAvailableOffline {
+        repository: String,
+        revision: String,
+        missing: Vec<String>,
+    },
+    /// Some files of a [`crate::model::download`] failed; each is paired
+    /// with its error.
+    #[error("{} file(s) failed to download from {repository}", failures.len())]
+    DownloadFailed {
+        repository: String,
+        failures: Vec<(String, Error)>,
+    },
+}
+
+fn missing_note(missing: &[String]) -> String {
+    match missing.len() {
+        0 => String::new(),
+        n => format!(" ({n} file(s) missing)"),
+    }
+}
+
+/// The library's result type.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// Classify a non-success response to `url`. The hub's `X-Error-Code`
+    /// header tells a gated repository or a missing repository apart from an
+    /// ordinary `401`/`403`.
+    pub fn from_response(url: &str, response: &reqwest::Response) -> Self {
+        let code = response
+            .headers()
+            .get("x-error-code")
+            .and_then(|v| v.to_str().ok());
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_retry_after);
+        Self::from_status(url, response.status(), code, retry_after)
+    }
+
+    /// [`Error::from_response`] given the response's parts.
+    pub fn from_status(
+        url: &str,
+        status: StatusCode,
+        error_code: Option<&str>,
+        retry_after: Option<Duration>,
+    ) -> Self {
+        let url = url.to_string();
+        match (status, error_code) {
+            (_, Some("GatedRepo")) => Self::Gated { url },
+            (_, Some("RepoNotFound" | "RevisionNotFound" | "EntryNotFound")) => {
+                Self::NotFound { url }
+            }
+            (StatusCode::NOT_FOUND, _) => Self::NotFound { url },
+            (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN, _) => Self::Unauthorized { url },
+            (StatusCode::TOO_MANY_REQUESTS, _) => Self::RateLimited { url, retry_after },
+            _ => Self::Http { status, url },
+        }
+    }
+
+    /// Whether retrying the operation might succeed: server errors, rate
+    /// limiting, timeouts and dropped connections. Missing, unauthorized and
+    /// gated resources, integrity failures and local errors will not change.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::RateLimited { .. } => true,
+            Self::Http { status, .. } => {
+                status.is_server_error() || *status == StatusCode::REQUEST_TIMEOUT
+            }
+            Self::Network(e) => match e.status() {
+                Some(status) => status.is_server_error(),
+                None => e.is_timeout() || e.is_connect() || e.is_body(),
+            },
+            Self::Io(e) => matches!(
+                e.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::UnexpectedEof
+                    | io::ErrorKind::Interrupted
+            ),
+            _ => false,
+        }
+    }
+
+    /// How long the server asked us to wait before retrying, if it did.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// Parse a `Retry-After` header given in delta-seconds. (The HTTP-date form
+/// is not supported and falls back to the retry policy's backoff.)
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(code: u16, error_code: Option<&str>) -> Error {
+        Error::from_status(
+            "https://huggingface.co/x",
+            StatusCode::from_u16(code).unwrap(),
+            error_code,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_from_status() {
+        assert!(matches!(status(404, None), Error::NotFound { .. }));
+        assert!(matches!(status(401, None), Error::Unauthorized { .. }));
+        assert!(matches!(status(403, None), Error::Unauthorized { .. }));
+        assert!(matches!(
+            status(401, Some("RepoNotFound")),
+            Error::NotFound { .. }
+        ));
+        assert!(matches!(
+            status(403, Some("GatedRepo")),
+            Error::Gated { .. }
+        ));
+        assert!(matches!(status(429, None), Error::RateLimited { .. }));
+        assert!(matches!(
+            status(502, None),
+            Error::Http {
+                status: StatusCode::BAD_GATEWAY,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(status(500, None).is_retryable());
+        assert!(status(502, None).is_retryable());
+        assert!(status(429, None).is_retryable());
+        assert!(!status(401, None).is_retryable());
+        assert!(!status(403, Some("GatedRepo")).is_retryable());
+        assert!(!status(404, None).is_retryable());
+        assert!(Error::Io(io::ErrorKind::ConnectionReset.into()).is_retryable());
+        assert!(!Error::Io(io::ErrorKind::PermissionDenied.into()).is_retryable());
+        assert!(!Error::Integrity {
+            file: "a".to_string(),
+            algorithm: "sha256",
+            expected: "b".to_string(),
+            actual: "c".to_string(),
+        }
+        .is_retryable());
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let limited = Error::from_status(
+            "u",
+            StatusCode::TOO_MANY_REQUESTS,
+            None,
+            Some(Duration::from_secs(3)),
+        );
+        assert_eq!(limited.retry_after(), Some(Duration::from_secs(3)));
+        assert_eq!(status(503, None).retry_after(), None);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+}
@@ -1,10 +1,25 @@
 //! possum — fetch and inspect 🤗 Hugging Face model artifacts as a library.
 //!
 //! The [`model`] module mirrors the CLI's subcommands as ordinary async
-//! functions: [`model::download`], [`model::list_files`], [`model::metadata`],
-//! [`model::revisions`], and [`model::search`].
+//! functions: [`model::download`], [`model::list_files`], [`model::list_tree`],
+//! [`model::metadata`], [`model::revisions`], and [`model::search`].
+//!
+//! Each of those builds a throwaway connection; a [`HubClient`] holds one
+//! connection pool plus the endpoint, token, user agent, timeouts, proxy and
+//! TLS certificates, and offers the same operations as methods, so long-lived
+//! callers can share it.
+//!
+//! [`auth::resolve_token`] finds a token the way `huggingface_hub` does
+//! (environment variables, then the stored token file).
+//!
+//! Failures are reported as a typed [`Error`], so callers can tell a missing
+//! repository from a gated one from a network outage without matching on
+//! message strings.
 
+pub mod auth;
+pub mod client;
+pub mod error;
 pub mod model;
 
-/// Boxed, thread-safe error used throughout the library.
-pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+pub use client::{HubClient, HubClientBuilder};
+pub use error::{Error, Result};
@@ -0,0 +1,101 @@
+//! A token-bucket limit on the bytes per second a download fetches, shared
+//! by every file (and segment) streaming at once.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Bounds the combined throughput of every stream it's charged by (see
+/// [`super::DownloadRequest::max_bandwidth`]). Clones share one bucket, so a
+/// caller can keep a clone and change the rate while a download runs.
+///
+/// The bucket holds up to a second's worth of bytes; a stream that takes
+/// more than there is waits until the debt is paid off at the current rate.
+#[derive(Debug, Clone)]
+pub struct BandwidthLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes per second; `0` means unlimited.
+    rate: u64,
+    /// Bytes that may be taken without waiting; negative while in debt.
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl BandwidthLimiter {
+    /// A limit of `bytes_per_sec` (`0` ⇒ unlimited).
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                rate: bytes_per_sec,
+                tokens: bytes_per_sec as f64,
+                refilled: Instant::now(),
+            })),
+        }
+    }
+
+    /// The current limit in bytes per second (`0` ⇒ unlimited).
+    pub fn rate(&self) -> u64 {
+        self.bucket.lock().unwrap().rate
+    }
+
+    /// Change the limit; it applies from the next chunk of every stream.
+    pub fn set_rate(&self, bytes_per_sec: u64) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill();
+        bucket.rate = bytes_per_sec;
+        bucket.tokens = bucket.tokens.min(bytes_per_sec as f64);
+    }
+
+    /// Take `bytes` from the bucket, waiting as long as that puts it in debt.
+    pub(crate) async fn acquire(&self, bytes: u64) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            if bucket.rate == 0 {
+                return;
+            }
+            bucket.refill();
+            bucket.tokens -= bytes as f64;
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-bucket.tokens / bucket.rate as f64)
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.refilled = now;
+        self.tokens = (self.tokens + elapsed * self.rate as f64).min(self.rate as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bandwidth_limiter() {
+        let limiter = BandwidthLimiter::new(10_000);
+        // A second's worth is available at once...
+        let started = Instant::now();
+        limiter.acquire(10_000).await;
+        assert!(started.elapsed() < Duration::from_millis(100));
+        // ...and more has to wait for the bucket to refill.
+        let started = Instant::now();
+        limiter.acquire(2_000).await;
+        assert!(started.elapsed() >= Duration::from_millis(150));
+
+        limiter.set_rate(0);
+        assert_eq!(limiter.rate(), 0);
+        let started = Instant::now();
+        limiter.acquire(1 << 30).await;
+        assert!(started.elapsed() < Duration::from_millis(100));
+    }
+}
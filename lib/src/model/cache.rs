@@ -0,0 +1,178 @@
+//! The Hugging Face hub cache layout shared with `huggingface_hub` and
+//! `transformers`:
+//!
+//! ```text
+//! <cache>/models--org--name/
+//!   blobs/<etag>                      file contents, named by sha256/git sha1
+//!   snapshots/<commit>/<path>         symlinks into `blobs/`
+//!   refs/<branch>                     the commit a branch/tag resolved to
+//! ```
+//!
+//! Blobs are content addressed, so a file shared by several revisions is
+//! stored (and downloaded) once.
+
+use std::env;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// The hub cache directory: `$HF_HUB_CACHE`, else `$HF_HOME/hub`, else
+/// `$XDG_CACHE_HOME/huggingface/hub`, else `~/.cache/huggingface/hub`.
+/// `None` if no home directory can be found.
+pub fn hub_cache_dir() -> Option<PathBuf> {
+    match env::var_os("HF_HUB_CACHE") {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => Some(hf_home()?.join("hub")),
+    }
+}
+
+/// The Hugging Face home directory: `$HF_HOME`, else
+/// `$XDG_CACHE_HOME/huggingface`, else `~/.cache/huggingface`. `None` if no
+/// home directory can be found.
+pub fn hf_home() -> Option<PathBuf> {
+    if let Some(home) = env::var_os("HF_HOME") {
+        return Some(PathBuf::from(home));
+    }
+    let cache = match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir()?.join(".cache"),
+    };
+    Some(cache.join("huggingface"))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+/// The cache folder name of a model repository: `org/name` ⇒
+/// `models--org--name`.
+pub fn repo_folder_name(repository: &str) -> String {
+    format!("models--{}", repository.replace('/', "--"))
+}
+
+/// One repository's folder within a hub cache.
+#[derive(Debug, Clone)]
+pub struct RepoCache {
+    dir: PathBuf,
+}
+
+impl RepoCache {
+    pub fn new(cache_dir: &Path, repository: &str) -> Self {
+        Self {
+            dir: cache_dir.join(repo_folder_name(repository)),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn blob_path(&self, etag: &str) -> PathBuf {
+        self.dir.join("blobs").join(etag)
+    }
+
+    pub fn snapshot_dir(&self, commit: &str) -> PathBuf {
+        self.dir.join("snapshots").join(commit)
+    }
+
+    pub fn ref_path(&self, revision: &str) -> PathBuf {
+        self.dir.join("refs").join(revision)
+    }
+
+    /// Record that `revision` resolved to `commit`. Nothing is written when
+    /// the revision already is the commit.
+    pub fn write_ref(&self, revision: &str, commit: &str) -> io::Result<()> {
+        if revision == commit {
+            return Ok(());
+        }
+        let path = self.ref_path(revision);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, commit)
+    }
+
+    /// Point `snapshots/<commit>/<file>` at `blobs/<etag>`, replacing any
+    /// existing entry.
+    pub fn link_snapshot(&self, commit: &str, file: &str, etag: &str) -> io::Result<()> {
+        let link = self.snapshot_dir(commit).join(file);
+        if let Some(parent) = link.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if link.symlink_metadata().is_ok() {
+            std::fs::remove_file(&link)?;
+        }
+        link_blob(&self.blob_path(etag), &blob_link_target(file, etag), &link)
+    }
+}
+
+/// The relative path from `snapshots/<commit>/<file>` to `blobs/<etag>`.
+pub fn blob_link_target(file: &str, etag: &str) -> PathBuf {
+    let depth = Path::new(file)
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .count();
+    // Up out of the file's own directories, `<commit>` and `snapshots`.
+    let mut target = PathBuf::new();
+    for _ in 0..depth + 1 {
+        target.push("..");
+    }
+    target.join("blobs").join(etag)
+}
+
+#[cfg(unix)]
+fn link_blob(_blob: &Path, target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+// Without (reliable) symlinks, fall back to a copy like `huggingface_hub`
+// does.
+#[cfg(not(unix))]
+fn link_blob(blob: &Path, _target: &Path, link: &Path) -> io::Result<()> {
+    std::fs::copy(blob, link).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_folder_name() {
+        assert_eq!(
+            repo_folder_name("deepseek-ai/DeepSeek-R1-Distill-Qwen-7B"),
+            "models--deepseek-ai--DeepSeek-R1-Distill-Qwen-7B"
+        );
+        assert_eq!(repo_folder_name("gpt2"), "models--gpt2");
+    }
+
+    #[test]
+    fn test_repo_cache_paths() {
+        let cache = RepoCache::new(Path::new("/hub"), "org/name");
+        assert_eq!(cache.dir(), Path::new("/hub/models--org--name"));
+        assert_eq!(
+            cache.blob_path("abc"),
+            Path::new("/hub/models--org--name/blobs/abc")
+        );
+        assert_eq!(
+            cache.snapshot_dir("123"),
+            Path::new("/hub/models--org--name/snapshots/123")
+        );
+        assert_eq!(
+            cache.ref_path("main"),
+            Path::new("/hub/models--org--name/refs/main")
+        );
+    }
+
+    #[test]
+    fn test_blob_link_target() {
+        assert_eq!(
+            blob_link_target("config.json", "abc"),
+            Path::new("../../blobs/abc")
+        );
+        assert_eq!(
+            blob_link_target("onnx/model.onnx", "abc"),
+            Path::new("../../../blobs/abc")
+        );
+    }
+}
@@ -1,21 +1,68 @@
-use crate::BoxError;
+use super::bandwidth::BandwidthLimiter;
+use super::cache::RepoCache;
+use super::info::{ModelInfo, Sibling};
+use super::lock::Lockfile;
+use super::plan::{plan, request_model_info, DownloadPlan, LocalState, PlannedFile};
+use super::progress::{Bars, FileProgress, ProgressEvent, ProgressSink, Silent};
+use super::report::{DownloadReport, FileOutcome, FileReport};
+use super::retry::RetryPolicy;
+use super::segmented::{done_segments, download_segments, SegmentError};
+use super::sidecar::{FileRecord, Sidecar};
+use super::verify::{self, ContentHasher, ExpectedDigest, Verification};
+use crate::{Error, HubClient, Result};
 use futures::stream::StreamExt;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use reqwest::Client;
+use reqwest::header::{HeaderName, CONTENT_RANGE, ETAG, IF_RANGE, RANGE};
+use reqwest::StatusCode;
 use serde_json::Value;
+use std::fmt;
 use std::fs;
+use std::future::Future;
+use std::io;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use tokio::io::AsyncWriteExt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
 
-/// Whether [`download`] renders progress bars.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+// HF's header carrying the LFS sha256 (or git blob id) of a resolved file.
+const X_LINKED_ETAG: HeaderName = HeaderName::from_static("x-linked-etag");
+
+/// How [`download`] reports its progress.
+#[derive(Clone, Default)]
 pub enum ProgressMode {
-    /// Render `indicatif` progress bars (the default).
+    /// Render `indicatif` progress bars (the default; see [`Bars`]).
     #[default]
     Auto,
     /// No progress UI (e.g. when an embedding caller wants silence).
     Hidden,
+    /// Send every [`ProgressEvent`] to a sink of the caller's (e.g. to drive
+    /// a GUI).
+    Sink(Arc<dyn ProgressSink>),
+}
+
+impl fmt::Debug for ProgressMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "Auto"),
+            Self::Hidden => write!(f, "Hidden"),
+            Self::Sink(_) => write!(f, "Sink(..)"),
+        }
+    }
+}
+
+/// Where [`download`] puts the files it fetches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageLayout {
+    /// Files are written directly under `to`, keeping their repository paths
+    /// (the default).
+    #[default]
+    Flat,
+    /// `to` is a Hugging Face hub cache (see [`super::cache::hub_cache_dir`]):
+    /// files become content-addressed `blobs/` linked from
+    /// `snapshots/<commit>/`, and `refs/<revision>` records the commit, so
+    /// `huggingface_hub`/`transformers` find them and revisions share blobs.
+    HfCache,
 }
 
 /// A request to download the files of a repository revision.
@@ -28,9 +75,11 @@ pub struct DownloadRequest {
     pub repository: String,
     /// Optional revision (branch/tag); `None` means `main`.
     pub revision: Option<String>,
-    /// Destination leaf directory; files are written directly under it.
+    /// Destination directory: a leaf directory for [`StorageLayout::Flat`],
+    /// the hub cache root for [`StorageLayout::HfCache`].
     pub to: PathBuf,
-    /// Hugging Face token (for gated repositories).
+    /// Hugging Face token (for gated repositories); overrides the
+    /// [`HubClient`]'s own token when set.
     pub token: Option<String>,
     /// Glob(s) of files to include (empty ⇒ all).
     pub include: Vec<String>,
@@ -38,10 +87,49 @@ pub struct DownloadRequest {
     pub exclude: Vec<String>,
     /// Maximum number of concurrent file downloads.
     pub concurrency: usize,
-    /// Base URL for the Hugging Face API.
+    /// Base URL for the Hugging Face API. Only the free [`download`] reads
+    /// this; [`HubClient::download`] uses the client's endpoint.
     pub api_base_url: String,
-    /// Whether to render progress bars.
+    /// How to report progress.
     pub progress: ProgressMode,
+    /// How files are laid out under `to`.
+    pub layout: StorageLayout,
+    /// How failed files are retried.
+    pub retry: RetryPolicy,
+    /// Files larger than this (in bytes) are fetched as concurrent
+    /// byte-range segments of this size (`0` ⇒ never segment).
+    pub segment_size: u64,
+    /// Maximum number of concurrent segment requests per file (`1` ⇒ every
+    /// file is fetched on a single connection).
+    pub connections_per_file: usize,
+    /// Fetch every selected file, even those already present and up to date.
+    pub force: bool,
+    /// Fetch exactly the files of this lock, from its commit, instead of
+    /// `revision`'s files selected by `include`/`exclude`; the hub must still
+    /// publish the locked size and digest of each (where it publishes them),
+    /// and every file fetched is verified against the lock's.
+    pub lock: Option<Lockfile>,
+    /// Shared between requests to bound their file downloads together (as
+    /// [`HubClient::sync`] does): each file holds a permit while it's fetched.
+    pub file_permits: Option<Arc<Semaphore>>,
+    /// After a successful download into a [`StorageLayout::Flat`] directory,
+    /// delete the files an earlier download put there that are no longer
+    /// selected (only files tracked in its sidecar are ever deleted).
+    pub prune: bool,
+    /// Stops the download when cancelled: files in flight stop streaming
+    /// (keeping their `.incomplete` temporaries to resume from) and files
+    /// not yet started aren't, each ending [`FileOutcome::Cancelled`].
+    /// Clones of the request share the token.
+    pub cancel: CancellationToken,
+    /// Caps the bytes per second fetched by all of the download's streams
+    /// together (and those of every other request sharing the limiter).
+    pub max_bandwidth: Option<BandwidthLimiter>,
+    /// Never contact the hub: list the revision's files from what earlier
+    /// downloads recorded in `to` (the lock, if any, the `.possum.json`
+    /// sidecar, or the hub cache's refs and snapshots) and fail with
+    /// [`Error::NotAvailableOffline`] unless every selected file is already
+    /// there. `force` has no effect.
+    pub offline: bool,
 }
 
 impl Default for DownloadRequest {
@@ -54,211 +142,945 @@ impl Default for DownloadRequest {
             include: Vec::new(),
             exclude: Vec::new(),
             concurrency: 4,
-            api_base_url: "https://huggingface.co".to_string(),
+            api_base_url: crate::client::DEFAULT_ENDPOINT.to_string(),
             progress: ProgressMode::Auto,
+            layout: StorageLayout::Flat,
+            retry: RetryPolicy::default(),
+            segment_size: 64 * 1024 * 1024,
+            connections_per_file: 4,
+            force: false,
+            lock: None,
+            file_permits: None,
+            prune: false,
+            cancel: CancellationToken::new(),
+            max_bandwidth: None,
+            offline: false,
         }
     }
 }
 
 /// List the files of a repository revision (the `siblings` of the model info).
+/// See [`list_download_files`] to list them offline.
 pub async fn list_files(
     repository: &str,
     revision: Option<&str>,
     token: Option<&str>,
     api_base_url: &str,
-) -> Result<Vec<String>, BoxError> {
-    let url = build_file_list_url(repository, revision, api_base_url);
+) -> Result<Vec<String>> {
+    HubClient::with_endpoint(api_base_url)?
+        .with_token(token)
+        .list_files(repository, revision)
+        .await
+}
 
-    tracing::info!(
-        "Getting a file list of {repository} (@ revision \"{}\")",
-        revision.unwrap_or("main")
-    );
-    tracing::debug!("File list URL: {url}");
+/// List the files `req` selects from, with their sizes and digests: those
+/// of its revision (or lock) as the hub lists them or, with
+/// [`DownloadRequest::offline`], as local state records them (the lock, the
+/// directory's sidecar or the hub cache's snapshot).
+pub async fn list_download_files(req: &DownloadRequest) -> Result<Vec<Sibling>> {
+    HubClient::with_endpoint(&req.api_base_url)?
+        .with_token(req.token.as_deref())
+        .list_download_files(req)
+        .await
+}
 
-    let client = Client::new();
-    let request = match token {
-        Some(t) => client.get(&url).bearer_auth(t),
-        None => client.get(&url),
-    };
+impl HubClient {
+    /// [`list_download_files`] through this client (`req.api_base_url` is
+    /// ignored, as by [`HubClient::download`]).
+    pub async fn list_download_files(&self, req: &DownloadRequest) -> Result<Vec<Sibling>> {
+        let model_info = match &req.token {
+            Some(token) => request_model_info(&self.with_token(Some(token)), req).await?,
+            None => request_model_info(self, req).await?,
+        };
+        Ok(model_info.siblings)
+    }
 
-    let response = request.send().await?;
-    if response.status().is_success() {
-        let model_info: Value = response.json().await?;
-        if let Some(siblings) = model_info["siblings"].as_array() {
-            let files: Vec<String> = siblings
-                .iter()
-                .filter_map(|f| f["rfilename"].as_str().map(|s| s.to_string()))
-                .collect();
-            if files.is_empty() {
+    /// List the files of a repository revision (the `siblings` of the model
+    /// info).
+    pub async fn list_files(
+        &self,
+        repository: &str,
+        revision: Option<&str>,
+    ) -> Result<Vec<String>> {
+        let model_info = self.model_info(repository, revision).await?;
+        Ok(model_info
+            .siblings
+            .into_iter()
+            .map(|sibling| sibling.rfilename)
+            .collect())
+    }
+
+    /// The model info of a revision, asked for with `?blobs=true` so each of
+    /// its `siblings` also carries its size, git blob id and LFS details.
+    pub async fn model_info(&self, repository: &str, revision: Option<&str>) -> Result<ModelInfo> {
+        let url = build_file_list_url(repository, revision, self.endpoint());
+
+        tracing::info!(
+            "Getting a file list of {repository} (@ revision \"{}\")",
+            revision.unwrap_or("main")
+        );
+        tracing::debug!("File list URL: {url}");
+
+        let response = self
+            .send_with(&url, |request| request.query(&[("blobs", "true")]))
+            .await?;
+        if response.status().is_success() {
+            let model_info: ModelInfo = response.json().await?;
+            if model_info.siblings.is_empty() {
                 tracing::info!("No files found in the repository");
             }
-            Ok(files)
+            Ok(model_info)
         } else {
-            tracing::info!("No files found in the repository");
-            Ok(vec![])
+            tracing::error!("Failed to list files: {}", response.status());
+            Err(Error::from_response(&url, &response))
         }
-    } else {
-        tracing::error!("Failed to list files: {}", response.status());
-        Err(format!("Failed to list files for {repository}").into())
     }
 }
 
 /// Download the selected files of a repository revision into `req.to`.
 ///
+/// The revision is resolved to a commit once, up front, and every file is
+/// fetched from that commit. The commit is recorded in the destination: in
+/// the `.possum.json` [`super::sidecar::Sidecar`] of a flat directory, in
+/// `refs/<revision>` of the hub cache.
+///
 /// File selection is driven by [`select_files`]; `req.concurrency` bounds the
-/// number of simultaneous downloads and `req.progress` controls the bars.
-/// Returns an error if any file fails or the repository cannot be listed.
-pub async fn download(req: &DownloadRequest) -> Result<(), BoxError> {
+/// number of simultaneous downloads and `req.progress` receives the
+/// download's [`ProgressEvent`]s.
+/// Each file is hashed as it streams and checked against the LFS sha256 or
+/// git blob id the Hub publishes for it; a mismatch fails the file and
+/// deletes its temporary.
+///
+/// Returns a [`DownloadReport`] with every file's outcome (including the
+/// files that failed or were cancelled; see [`DownloadReport::into_result`]),
+/// or an error if the download couldn't start: the repository can't be
+/// listed, the files won't fit, it was cancelled while planning, and so on.
+/// No events are sent for a download that couldn't start; one that did sends
+/// [`ProgressEvent::Planned`] first and [`ProgressEvent::Finished`] last,
+/// even if recording its outcome on disk then fails.
+///
+/// With [`StorageLayout::HfCache`] a file whose blob is already cached is not
+/// fetched again; it is only linked into the revision's snapshot.
+pub async fn download(req: &DownloadRequest) -> Result<DownloadReport> {
+    HubClient::with_endpoint(&req.api_base_url)?
+        .download(req)
+        .await
+}
+
+impl HubClient {
+    /// [`download`] through this client's connection pool and endpoint
+    /// (`req.api_base_url` is ignored).
+    pub async fn download(&self, req: &DownloadRequest) -> Result<DownloadReport> {
+        match &req.token {
+            Some(token) => download_from(&self.with_token(Some(token)), req).await,
+            None => download_from(self, req).await,
+        }
+    }
+}
+
+async fn download_from(hub: &HubClient, req: &DownloadRequest) -> Result<DownloadReport> {
+    let started = Instant::now();
     if !req.to.exists() {
         fs::create_dir_all(&req.to)?;
         tracing::info!("Created directory: {}", req.to.display());
     }
 
-    let files = list_files(
-        &req.repository,
-        req.revision.as_deref(),
-        req.token.as_deref(),
-        &req.api_base_url,
-    )
-    .await?;
+    let mut plan = until_cancelled(&req.cancel, plan(hub, req)).await??;
+    // Offline, everything is in place or the download fails here, and
+    // nothing can be fetched again however forced.
+    let unforced;
+    let req = if req.offline {
+        check_offline(&plan)?;
+        unforced = DownloadRequest {
+            force: false,
+            ..req.clone()
+        };
+        &unforced
+    } else {
+        req
+    };
 
-    let has_safetensor = files
-        .iter()
-        .any(|file| file.starts_with("model") && file.ends_with(".safetensors"));
+    // Fetch every file from the commit the revision resolved to, so a branch
+    // moving mid-download can't mix files from two commits.
+    let pinned;
+    let req = match &plan.commit {
+        Some(commit) => {
+            tracing::info!("Resolved revision \"{}\" to commit {commit}", plan.revision);
+            pinned = DownloadRequest {
+                revision: Some(commit.clone()),
+                ..req.clone()
+            };
+            &pinned
+        }
+        None => {
+            tracing::warn!(
+                "The hub did not report a commit for revision \"{}\"; downloading from the revision itself",
+                plan.revision
+            );
+            req
+        }
+    };
+
+    let cache = match req.layout {
+        StorageLayout::Flat => None,
+        StorageLayout::HfCache => {
+            let commit = plan.commit.as_deref().ok_or_else(|| {
+                Error::InvalidResponse(format!(
+                    "{}: the hub did not report a commit sha, which the cache layout needs",
+                    req.repository
+                ))
+            })?;
+            let cache = RepoCache::new(&req.to, &req.repository);
+            Some((cache, commit.to_string()))
+        }
+    };
+    let needed = if req.force {
+        plan.files.iter().filter_map(|f| f.size).sum()
+    } else {
+        plan.bytes_to_fetch()
+    };
+    check_free_space(&req.to, needed)?;
 
-    let files = select_files(files, &req.include, &req.exclude, has_safetensor)?;
-    if files.is_empty() {
+    // Everything that can stop the download from starting is checked by
+    // now: a sink that sees `Planned` always sees `Finished` too.
+    let bars;
+    let sink: &dyn ProgressSink = match &req.progress {
+        ProgressMode::Auto => {
+            bars = Bars::default();
+            &bars
+        }
+        ProgressMode::Hidden => &Silent,
+        ProgressMode::Sink(sink) => sink.as_ref(),
+    };
+    sink.event(&ProgressEvent::Planned { plan: &plan });
+
+    let mut report = DownloadReport {
+        repository: req.repository.clone(),
+        revision: plan.revision.clone(),
+        commit: plan.commit.clone(),
+        endpoints: vec![],
+        files: Vec::with_capacity(plan.files.len()),
+        pruned: vec![],
+        bytes: 0,
+        duration: Duration::ZERO,
+    };
+    if plan.files.is_empty() {
         tracing::warn!("No files matched the selection; nothing to download");
-        return Ok(());
+        report.duration = started.elapsed();
+        sink.event(&ProgressEvent::Finished { report: &report });
+        return Ok(report);
     }
 
+    // In a flat directory, the sidecar records the commit and each file put
+    // (or found) in place, so the next run can skip it without hashing it.
+    // It's kept in memory while the files download and written once at the
+    // end.
+    let sidecar = match req.layout {
+        StorageLayout::Flat => {
+            let mut sidecar = Sidecar::load_for(&req.to, &req.repository);
+            sidecar.repository = Some(req.repository.clone());
+            Some(Mutex::new(sidecar))
+        }
+        StorageLayout::HfCache => None,
+    };
+
     let concurrency = req.concurrency.max(1);
-    let client = Arc::new(Client::new());
-    let mp = Arc::new(MultiProgress::new());
 
     tracing::info!(
         "Downloading {} file(s) from {} (@ revision \"{}\") [concurrency {concurrency}]",
-        files.len(),
+        plan.files.len(),
         req.repository,
-        req.revision.as_deref().unwrap_or("main")
+        plan.revision
     );
 
-    let results: Vec<Result<(), BoxError>> = futures::stream::iter(files.into_iter().map(|file| {
-        let client = Arc::clone(&client);
-        let mp = Arc::clone(&mp);
-        let token = req.token.clone();
-        let to = req.to.clone();
-        let repository = req.repository.clone();
-        let revision = req.revision.clone();
-        let api_base_url = req.api_base_url.clone();
-        let progress = req.progress;
-
-        async move {
-            download_file(
-                &client,
-                &mp,
-                &repository,
-                revision.as_deref(),
-                &file,
-                token.as_deref(),
-                &to,
-                &api_base_url,
-                progress,
-            )
-            .await
-            .inspect_err(|e| tracing::error!("Failed to download {file}: {e}"))
-        }
-    }))
-    .buffer_unordered(concurrency)
-    .collect()
-    .await;
+    let mut results: Vec<(usize, FileReport)> =
+        futures::stream::iter(std::mem::take(&mut plan.files).into_iter().enumerate().map(
+            |(i, planned)| {
+                let (cache, sidecar) = (cache.as_ref(), sidecar.as_ref());
 
-    let failures = results.iter().filter(|r| r.is_err()).count();
+                async move {
+                    let started = Instant::now();
+                    let PlannedFile {
+                        file,
+                        size,
+                        digest,
+                        local,
+                    } = planned;
+                    let hex = digest.as_ref().map(|d| d.hex().to_string());
+                    let (result, bytes, endpoint) =
+                        if sidecar.is_some() && local == LocalState::UpToDate && !req.force {
+                            tracing::info!("{file}: already up to date");
+                            (Ok(Verification::UpToDate), 0, None)
+                        } else {
+                            let progress = FileProgress::new(sink, &file);
+                            let result = async {
+                                let _permit = match &req.file_permits {
+                                    Some(permits) => Some(
+                                        until_cancelled(&req.cancel, permits.acquire())
+                                            .await?
+                                            .expect("never closed"),
+                                    ),
+                                    None => None,
+                                };
+                                if req.cancel.is_cancelled() {
+                                    return Err(Error::Cancelled);
+                                }
+                                fetch(hub, &progress, req, cache, &file, digest, size).await
+                            }
+                            .await;
+                            (result, progress.fetched(), progress.endpoint())
+                        };
+                    let result = match (result, sidecar) {
+                        (Ok(verification), Some(sidecar)) => {
+                            record(&req.to, sidecar, &file, hex.as_deref()).map(|()| verification)
+                        }
+                        (result, _) => result,
+                    };
+                    match &result {
+                        Ok(verification) => sink.event(&ProgressEvent::FileFinished {
+                            file: &file,
+                            verification: *verification,
+                        }),
+                        Err(Error::Cancelled) => {
+                            tracing::info!("{file}: cancelled");
+                            sink.event(&ProgressEvent::FileFailed {
+                                file: &file,
+                                error: &Error::Cancelled,
+                            });
+                        }
+                        Err(error) => {
+                            tracing::error!("Failed to download {file}: {error}");
+                            sink.event(&ProgressEvent::FileFailed { file: &file, error });
+                        }
+                    }
+                    let report = FileReport {
+                        file,
+                        outcome: FileOutcome::of(result),
+                        bytes,
+                        endpoint,
+                        duration: started.elapsed(),
+                    };
+                    (i, report)
+                }
+            },
+        ))
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+    results.sort_by_key(|(i, _)| *i);
+    report.files = results.into_iter().map(|(_, file)| file).collect();
+    report.bytes = report.files.iter().map(|f| f.bytes).sum();
+    for endpoint in report.files.iter().filter_map(|f| f.endpoint.as_ref()) {
+        if !report.endpoints.contains(endpoint) {
+            report.endpoints.push(endpoint.clone());
+        }
+    }
 
     tracing::info!(
         "Finished downloading from {} (@ revision \"{}\")",
         req.repository,
-        req.revision.as_deref().unwrap_or("main")
+        plan.revision
     );
 
-    if failures > 0 {
-        return Err(format!(
-            "{failures} file(s) failed to download from {}",
-            req.repository
-        )
-        .into());
+    let settled = settle(req, plan, sidecar, cache.as_ref(), &mut report);
+
+    report.duration = started.elapsed();
+    sink.event(&ProgressEvent::Finished { report: &report });
+    settled.map(|()| report)
+}
+
+// Once the files are done, save what's now in a flat directory. Only a
+// complete download is of the plan's commit: then the sidecar (or, in the
+// hub cache, the revision's ref) is pointed at it and the files no longer
+// selected are pruned; a partial one keeps naming whatever commit was last
+// complete.
+fn settle(
+    req: &DownloadRequest,
+    plan: DownloadPlan,
+    sidecar: Option<Mutex<Sidecar>>,
+    cache: Option<&(RepoCache, String)>,
+    report: &mut DownloadReport,
+) -> Result<()> {
+    if let Some(sidecar) = sidecar {
+        let mut sidecar = sidecar.into_inner().unwrap();
+        if report.is_success() {
+            sidecar.revision = Some(plan.revision.clone());
+            sidecar.commit = plan.commit;
+            // An offline listing comes from here (or from a lock) already.
+            if !req.offline {
+                sidecar.listing = plan.listing;
+            }
+            if !plan.prune.is_empty() {
+                prune(&req.to, &mut sidecar, &plan.prune)?;
+                report.pruned = plan.prune;
+            }
+        }
+        sidecar.save(&req.to)?;
+    }
+    // A lock pins an old commit of the revision; pointing the ref back at it
+    // would roll back whatever else uses the cache.
+    if let Some((cache, commit)) = cache {
+        if report.is_success() && req.lock.is_none() {
+            cache.write_ref(&plan.revision, commit)?;
+        }
     }
+    Ok(())
+}
 
+// Offline, nothing can be fetched: fail unless every selected file is
+// already in place (and something was selected at all).
+fn check_offline(plan: &DownloadPlan) -> Result<()> {
+    let missing: Vec<String> = plan
+        .files
+        .iter()
+        .filter(|f| f.local != LocalState::UpToDate)
+        .map(|f| f.file.clone())
+        .collect();
+    if plan.files.is_empty() || !missing.is_empty() {
+        return Err(Error::NotAvailableOffline {
+            repository: plan.repository.clone(),
+            revision: plan.revision.clone(),
+            missing,
+        });
+    }
     Ok(())
 }
 
-// Download a single file: GET it, create any nested parent directories,
-// stream it to a temporary `.incomplete` sibling, and rename on success so an
-// interrupted download never leaves a truncated file that looks complete.
-#[allow(clippy::too_many_arguments)]
-async fn download_file(
-    client: &Client,
-    mp: &MultiProgress,
-    repository: &str,
-    revision: Option<&str>,
+// Delete `files` from `dir` along with any temporaries of theirs, forget them
+// in the sidecar, and remove the directories they leave empty.
+fn prune(dir: &Path, sidecar: &mut Sidecar, files: &[String]) -> Result<()> {
+    for file in files {
+        let path = dir.join(file);
+        for path in [
+            with_suffix(&path, ".incomplete"),
+            with_suffix(&path, ".incomplete.json"),
+            path.clone(),
+        ] {
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        tracing::info!("Pruned {file}");
+        sidecar.files.remove(file);
+        let mut parent = path.parent();
+        while let Some(p) = parent.filter(|p| *p != dir) {
+            if fs::remove_dir(p).is_err() {
+                break;
+            }
+            parent = p.parent();
+        }
+    }
+    Ok(())
+}
+
+/// Whether the repository has `model*.safetensors` weights, in which case
+/// the `*.bin`/`*.pt` copies of them are skipped (see [`should_ignore_file`]).
+pub fn has_safetensors_weights(files: &[String]) -> bool {
+    files
+        .iter()
+        .any(|file| file.starts_with("model") && file.ends_with(".safetensors"))
+}
+
+// The bytes an earlier attempt left in the `.incomplete` temporary of
+// `file_path`. A segmented temporary is pre-allocated to the full size, so
+// what it holds is the segments its state records as done.
+pub(super) fn partial_len(file_path: &Path) -> u64 {
+    let Ok(meta) = fs::metadata(with_suffix(file_path, ".incomplete")) else {
+        return 0;
+    };
+    let state = fs::read(with_suffix(file_path, ".incomplete.json"))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or(Value::Null);
+    if state["segments"].is_array() {
+        done_segments(&state).iter().map(|(s, e)| e - s + 1).sum()
+    } else {
+        meta.len()
+    }
+}
+
+// Fail with `Error::InsufficientSpace` unless `needed` more bytes fit on the
+// filesystem holding `dir`. Filesystems that can't report their free space
+// are not checked.
+fn check_free_space(dir: &Path, needed: u64) -> Result<()> {
+    match fs4::available_space(dir) {
+        Ok(available) if available < needed => Err(Error::InsufficientSpace {
+            path: dir.to_path_buf(),
+            needed,
+            available,
+        }),
+        Ok(available) => {
+            tracing::info!("{needed} byte(s) to download, {available} available");
+            Ok(())
+        }
+        Err(e) => {
+            tracing::warn!("Cannot check free space in {}: {e}", dir.display());
+            Ok(())
+        }
+    }
+}
+
+// Record the file now in place at `dir/file` in the directory's sidecar
+// (which is saved once the download is over).
+fn record(dir: &Path, sidecar: &Mutex<Sidecar>, file: &str, digest: Option<&str>) -> Result<()> {
+    let record = FileRecord::of(&dir.join(file), digest)?;
+    let mut sidecar = sidecar.lock().unwrap();
+    sidecar.files.insert(file.to_string(), record);
+    Ok(())
+}
+
+// Fetch one selected file into its place in `req.layout`. In the hub cache
+// the file is downloaded to `blobs/<etag>` unless that blob is already there,
+// then linked from the commit's snapshot.
+async fn fetch(
+    hub: &HubClient,
+    progress: &FileProgress<'_>,
+    req: &DownloadRequest,
+    cache: Option<&(RepoCache, String)>,
     file: &str,
-    token: Option<&str>,
-    to: &Path,
-    api_base_url: &str,
-    progress: ProgressMode,
-) -> Result<(), BoxError> {
-    let url = build_download_url(repository, revision, file, api_base_url);
+    expected: Option<ExpectedDigest>,
+    expected_size: Option<u64>,
+) -> Result<Verification> {
+    let Some((cache, commit)) = cache else {
+        let file_path = req.to.join(file);
+        return download_with_retry(
+            hub,
+            progress,
+            req,
+            file,
+            expected,
+            expected_size,
+            &file_path,
+        )
+        .await;
+    };
 
-    let request = match token {
-        Some(t) => client.get(&url).bearer_auth(t),
-        None => client.get(&url),
+    let etag = match &expected {
+        Some(digest) => digest.hex().to_string(),
+        None => {
+            return Err(Error::InvalidResponse(format!(
+                "{file}: the hub published no etag to cache it under"
+            )))
+        }
+    };
+    let blob = cache.blob_path(&etag);
+    let verification = if blob.exists() && !req.force {
+        Verification::Cached
+    } else {
+        download_with_retry(hub, progress, req, file, expected, expected_size, &blob).await?
     };
+    cache.link_snapshot(commit, file, &etag)?;
+    Ok(verification)
+}
 
-    let response = request.send().await?;
-    if !response.status().is_success() {
-        return Err(format!("{file}: HTTP {}", response.status()).into());
+// Download a file under `req.retry`: transient failures are retried after a
+// backoff (or the server's `Retry-After`), each attempt resuming from the
+// `.incomplete` temporary the one before left behind.
+async fn download_with_retry(
+    hub: &HubClient,
+    progress: &FileProgress<'_>,
+    req: &DownloadRequest,
+    file: &str,
+    expected: Option<ExpectedDigest>,
+    expected_size: Option<u64>,
+    file_path: &Path,
+) -> Result<Verification> {
+    let max_attempts = req.retry.max_attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        let result = download_file(
+            hub,
+            req,
+            file,
+            expected.clone(),
+            expected_size,
+            file_path,
+            progress,
+        )
+        .await;
+        match result {
+            Ok(verification) => return Ok(verification),
+            Err(e) if attempt < max_attempts && e.is_retryable() => {
+                let delay = req.retry.delay(attempt, e.retry_after());
+                tracing::warn!(
+                    "{file}: attempt {attempt}/{max_attempts} failed ({e}); retrying in {delay:?}"
+                );
+                progress.retrying(attempt, delay, &e);
+                until_cancelled(&req.cancel, tokio::time::sleep(delay)).await?;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
     }
+}
+
+// Download a single file to `file_path`: GET it, create any nested parent
+// directories, stream it to a temporary `.incomplete` sibling, and rename on
+// success so an interrupted download never leaves a truncated file that looks
+// complete.
+//
+// A leftover `.incomplete` from an earlier attempt is resumed with a
+// `Range: bytes=N-` request (guarded by `If-Range` with the ETag recorded
+// when the temporary was started). Anything other than a `206` whose
+// `Content-Range` starts at `N` and whose ETag still matches restarts the
+// file from byte zero.
+//
+// The contents are hashed as they stream (after re-reading any resumed
+// prefix) and compared with `expected`, falling back to the digest in an
+// `X-Linked-Etag` header; on a mismatch the temporary is deleted.
+//
+// A file of known size above `req.segment_size` is instead fetched in
+// parallel segments (see `segmented`) and hashed once complete, falling back
+// to the single-stream path if the server won't serve ranges.
+async fn download_file(
+    hub: &HubClient,
+    req: &DownloadRequest,
+    file: &str,
+    expected: Option<ExpectedDigest>,
+    expected_size: Option<u64>,
+    file_path: &Path,
+    progress: &FileProgress<'_>,
+) -> Result<Verification> {
+    let url = build_download_url(
+        &req.repository,
+        req.revision.as_deref(),
+        file,
+        hub.endpoint(),
+    );
 
-    let file_path = to.join(file);
     if let Some(parent) = file_path.parent() {
         tokio::fs::create_dir_all(parent).await?;
     }
+    let tmp_path = with_suffix(file_path, ".incomplete");
+    let state_path = with_suffix(file_path, ".incomplete.json");
 
-    let total_size = response.content_length().unwrap_or(0);
-    let progress_bar = match progress {
-        ProgressMode::Auto => {
-            let pb = mp.add(ProgressBar::new(total_size));
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta}) - {msg}")
-                    .expect("Failed to create ProgressBar template")
-                    .progress_chars("#>-"),
-            );
-            pb.set_message(file.to_string());
-            pb
+    if let Some(size) = expected_size.filter(|&size| is_segmented(req, size)) {
+        let result =
+            download_segments(hub, req, file, &url, size, &tmp_path, &state_path, progress).await;
+        match result {
+            Ok(()) => {
+                let hasher = match expected {
+                    Some(digest) => {
+                        let mut hasher = ContentHasher::new(&digest, size);
+                        hash_file(&tmp_path, &mut hasher).await?;
+                        Some((digest, hasher))
+                    }
+                    None => None,
+                };
+                return finish(file, file_path, &tmp_path, &state_path, Some(size), hasher).await;
+            }
+            Err(SegmentError::Abandoned(reason)) => {
+                tracing::info!("{reason}; downloading on a single connection");
+                let _ = tokio::fs::remove_file(&tmp_path).await;
+                let _ = tokio::fs::remove_file(&state_path).await;
+            }
+            Err(SegmentError::Failed(e)) => return Err(e),
         }
-        ProgressMode::Hidden => ProgressBar::hidden(),
+    }
+
+    // A temporary left by a segmented download is pre-allocated, so its
+    // length says nothing about how much of it is filled in.
+    let state = read_state(&state_path).await;
+    let mut offset = match tokio::fs::metadata(&tmp_path).await {
+        Ok(meta) if state["segments"].is_null() => meta.len(),
+        _ => 0,
     };
+    let saved_etag = if offset > 0 {
+        state["etag"].as_str().map(String::from)
+    } else {
+        None
+    };
+
+    let mut response = until_cancelled(
+        &req.cancel,
+        send_get(hub, progress, &url, offset, None, saved_etag.as_deref()),
+    )
+    .await??;
+    if offset > 0 {
+        let status = response.status();
+        let content_range = header_str(&response, CONTENT_RANGE);
+        let etag = header_str(&response, ETAG);
+        if can_resume(
+            status,
+            content_range.as_deref(),
+            etag.as_deref(),
+            offset,
+            saved_etag.as_deref(),
+        ) {
+            tracing::info!("{file}: resuming from byte {offset}");
+        } else {
+            tracing::info!("{file}: cannot resume from byte {offset} (HTTP {status}); restarting");
+            offset = 0;
+            // A plain `200` already carries the whole body; anything else
+            // (a mismatched `206`, a `416`, ...) needs a fresh request.
+            if status != StatusCode::OK {
+                response =
+                    until_cancelled(&req.cancel, send_get(hub, progress, &url, 0, None, None))
+                        .await??;
+            }
+        }
+    }
+
+    if !response.status().is_success() {
+        return Err(Error::from_response(&url, &response));
+    }
+
+    if offset == 0 {
+        save_etag(&state_path, header_str(&response, ETAG).as_deref()).await?;
+    }
 
-    let mut tmp_os = file_path.clone().into_os_string();
-    tmp_os.push(".incomplete");
-    let tmp_path = PathBuf::from(tmp_os);
+    let total_size = header_str(&response, CONTENT_RANGE)
+        .filter(|_| offset > 0)
+        .and_then(|range| parse_content_range(&range))
+        .and_then(|(_, total)| total)
+        .or_else(|| response.content_length().map(|len| offset + len))
+        .unwrap_or(0);
+    progress.start(total_size, offset);
 
-    let mut dest = tokio::fs::File::create(&tmp_path).await?;
+    let expected = expected.or_else(|| {
+        header_str(&response, X_LINKED_ETAG).and_then(|etag| ExpectedDigest::from_etag(&etag))
+    });
+    let mut hasher = match expected {
+        Some(digest) => {
+            let mut hasher = ContentHasher::new(&digest, expected_size.unwrap_or(total_size));
+            if offset > 0 {
+                hash_file(&tmp_path, &mut hasher).await?;
+            }
+            Some((digest, hasher))
+        }
+        None => None,
+    };
+
+    let mut dest = if offset > 0 {
+        tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&tmp_path)
+            .await?
+    } else {
+        tokio::fs::File::create(&tmp_path).await?
+    };
     let mut content = response.bytes_stream();
-    while let Some(chunk) = content.next().await {
-        let chunk = chunk?;
+    loop {
+        let chunk = match until_cancelled(&req.cancel, content.next()).await {
+            Ok(Some(Ok(chunk))) => chunk,
+            Ok(None) => break,
+            Ok(Some(Err(e))) => {
+                // Keep what we have on disk so the next attempt (or run) can
+                // resume.
+                dest.flush().await?;
+                return Err(e.into());
+            }
+            Err(e) => {
+                dest.flush().await?;
+                return Err(e);
+            }
+        };
         tokio::io::copy(&mut chunk.as_ref(), &mut dest).await?;
-        progress_bar.inc(chunk.len() as u64);
+        if let Some((_, hasher)) = hasher.as_mut() {
+            hasher.update(&chunk);
+        }
+        progress.advance(chunk.len() as u64);
+        if let Err(e) = throttle(req, chunk.len() as u64).await {
+            dest.flush().await?;
+            return Err(e);
+        }
     }
     dest.flush().await?;
     drop(dest);
-    tokio::fs::rename(&tmp_path, &file_path).await?;
 
-    progress_bar.finish_with_message(format!("Downloaded: {file}"));
+    finish(
+        file,
+        file_path,
+        &tmp_path,
+        &state_path,
+        expected_size,
+        hasher,
+    )
+    .await
+}
+
+// Run `fut` to completion, or fail with `Error::Cancelled` as soon as `cancel`
+// is (dropping `fut`).
+pub(super) async fn until_cancelled<F: Future>(
+    cancel: &CancellationToken,
+    fut: F,
+) -> Result<F::Output> {
+    cancel
+        .run_until_cancelled(fut)
+        .await
+        .ok_or(Error::Cancelled)
+}
+
+// Wait until `bytes` more fit within `req.max_bandwidth`.
+pub(super) async fn throttle(req: &DownloadRequest, bytes: u64) -> Result<()> {
+    match &req.max_bandwidth {
+        Some(limiter) => until_cancelled(&req.cancel, limiter.acquire(bytes)).await,
+        None => Ok(()),
+    }
+}
+
+// Whether a file of `size` bytes is fetched in segments.
+fn is_segmented(req: &DownloadRequest, size: u64) -> bool {
+    req.connections_per_file > 1 && req.segment_size > 0 && size > req.segment_size
+}
+
+// Check a complete temporary's size and hash and rename it into place, or
+// delete it if either doesn't match.
+async fn finish(
+    file: &str,
+    file_path: &Path,
+    tmp_path: &Path,
+    state_path: &Path,
+    expected_size: Option<u64>,
+    hasher: Option<(ExpectedDigest, ContentHasher)>,
+) -> Result<Verification> {
+    let size = tokio::fs::metadata(tmp_path).await?.len();
+    if let Some(expected) = expected_size.filter(|&expected| expected != size) {
+        let _ = tokio::fs::remove_file(tmp_path).await;
+        let _ = tokio::fs::remove_file(state_path).await;
+        return Err(Error::Integrity {
+            file: file.to_string(),
+            algorithm: "size",
+            expected: expected.to_string(),
+            actual: size.to_string(),
+        });
+    }
+    let verification = match hasher {
+        Some((digest, hasher)) => match verify::check(file, &digest, &hasher.finalize_hex()) {
+            Ok(verification) => verification,
+            Err(e) => {
+                let _ = tokio::fs::remove_file(tmp_path).await;
+                let _ = tokio::fs::remove_file(state_path).await;
+                return Err(e);
+            }
+        },
+        None => Verification::Unverified,
+    };
+
+    tokio::fs::rename(tmp_path, file_path).await?;
+    let _ = tokio::fs::remove_file(state_path).await;
+
+    Ok(verification)
+}
+
+// Feed the bytes of a file (e.g. those already in a temporary) to `hasher`.
+pub(super) async fn hash_file(path: &Path, hasher: &mut ContentHasher) -> Result<()> {
+    let mut partial = tokio::fs::File::open(path).await?;
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = partial.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        hasher.update(&buf[..n]);
+    }
+}
+
+// GET `url`, asking for the bytes from `offset` (up to `end`, inclusive)
+// when resuming or fetching a segment, and note in `progress` which endpoint
+// answered.
+pub(super) async fn send_get(
+    hub: &HubClient,
+    progress: &FileProgress<'_>,
+    url: &str,
+    offset: u64,
+    end: Option<u64>,
+    etag: Option<&str>,
+) -> Result<reqwest::Response> {
+    let (response, endpoint) = hub
+        .send_from(url, |mut request| {
+            if offset > 0 || end.is_some() {
+                let end = end.map(|e| e.to_string()).unwrap_or_default();
+                request = request.header(RANGE, format!("bytes={offset}-{end}"));
+                // `If-Range` requires a strong validator.
+                if let Some(etag) = etag.filter(|e| !e.starts_with("W/")) {
+                    request = request.header(IF_RANGE, etag);
+                }
+            }
+            request
+        })
+        .await?;
+    progress.served_by(endpoint);
+    Ok(response)
+}
+
+pub(super) fn header_str(response: &reqwest::Response, name: HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+}
+
+// The state recorded beside a `.incomplete` temporary (the ETag it was
+// started from and, for a segmented download, the segments done), or `Null`.
+pub(super) async fn read_state(state_path: &Path) -> Value {
+    match tokio::fs::read(state_path).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        Err(_) => Value::Null,
+    }
+}
+
+async fn save_etag(state_path: &Path, etag: Option<&str>) -> Result<()> {
+    match etag {
+        Some(etag) => {
+            let state = serde_json::json!({ "etag": etag });
+            tokio::fs::write(state_path, state.to_string()).await?;
+        }
+        None => {
+            let _ = tokio::fs::remove_file(state_path).await;
+        }
+    }
     Ok(())
 }
 
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut os = path.as_os_str().to_owned();
+    os.push(suffix);
+    PathBuf::from(os)
+}
+
+/// Whether a response to a `Range: bytes={offset}-` request continues the
+/// partial download: it must be a `206` whose `Content-Range` starts at
+/// `offset`, and if both sides know an ETag, they must agree.
+pub fn can_resume(
+    status: StatusCode,
+    content_range: Option<&str>,
+    etag: Option<&str>,
+    offset: u64,
+    saved_etag: Option<&str>,
+) -> bool {
+    if status != StatusCode::PARTIAL_CONTENT {
+        return false;
+    }
+    let starts_at_offset = content_range
+        .and_then(parse_content_range)
+        .is_some_and(|((start, _), _)| start == offset);
+    let same_etag = match (etag, saved_etag) {
+        (Some(now), Some(then)) => now == then,
+        _ => true,
+    };
+    starts_at_offset && same_etag
+}
+
+/// Parse a `Content-Range: bytes start-end/total` header value into
+/// `((start, end), total)`; `total` is `None` for `*`.
+pub fn parse_content_range(value: &str) -> Option<((u64, u64), Option<u64>)> {
+    let rest = value.trim().strip_prefix("bytes ")?;
+    let (range, total) = rest.split_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    let total = match total.trim() {
+        "*" => None,
+        t => Some(t.parse().ok()?),
+    };
+    Some((
+        (start.trim().parse().ok()?, end.trim().parse().ok()?),
+        total,
+    ))
+}
+
 pub fn build_file_list_url(repository: &str, revision: Option<&str>, api_base_url: &str) -> String {
     let mut url = format!("{api_base_url}/api/models/{repository}");
     if let Some(rev) = revision {
@@ -299,7 +1121,7 @@ pub fn select_files(
     include: &[String],
     exclude: &[String],
     has_safetensors: bool,
-) -> Result<Vec<String>, glob::PatternError> {
+) -> Result<Vec<String>> {
     let includes = compile_patterns(include)?;
     let excludes = compile_patterns(exclude)?;
 
@@ -313,8 +1135,11 @@ pub fn select_files(
     Ok(selected)
 }
 
-fn compile_patterns(patterns: &[String]) -> Result<Vec<glob::Pattern>, glob::PatternError> {
-    patterns.iter().map(|p| glob::Pattern::new(p)).collect()
+fn compile_patterns(patterns: &[String]) -> Result<Vec<glob::Pattern>> {
+    patterns
+        .iter()
+        .map(|p| glob::Pattern::new(p).map_err(Error::from))
+        .collect()
 }
 
 #[cfg(test)]
@@ -475,9 +1300,89 @@ mod tests {
         assert_eq!(out, vec!["pytorch_model.bin"]);
     }
 
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(
+            parse_content_range("bytes 100-199/200"),
+            Some(((100, 199), Some(200)))
+        );
+        assert_eq!(parse_content_range("bytes 0-9/*"), Some(((0, 9), None)));
+        assert_eq!(parse_content_range("bytes */200"), None);
+        assert_eq!(parse_content_range("items 0-9/10"), None);
+    }
+
+    #[test]
+    fn test_can_resume() {
+        let ok = StatusCode::PARTIAL_CONTENT;
+        assert!(can_resume(ok, Some("bytes 5-9/10"), None, 5, None));
+        assert!(can_resume(
+            ok,
+            Some("bytes 5-9/10"),
+            Some("\"a\""),
+            5,
+            Some("\"a\"")
+        ));
+        assert!(!can_resume(
+            ok,
+            Some("bytes 5-9/10"),
+            Some("\"b\""),
+            5,
+            Some("\"a\"")
+        ));
+        assert!(!can_resume(ok, Some("bytes 0-9/10"), None, 5, None));
+        assert!(!can_resume(ok, None, None, 5, None));
+        assert!(!can_resume(StatusCode::OK, None, None, 5, None));
+    }
+
     #[test]
     fn test_select_files_invalid_glob_errors() {
         let files = vec!["a".to_string()];
         assert!(select_files(files, &["[".to_string()], &[], false).is_err());
     }
+
+    #[test]
+    fn test_has_safetensors_weights() {
+        let files = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert!(has_safetensors_weights(&files(&[
+            "model-00001-of-00002.safetensors"
+        ])));
+        assert!(!has_safetensors_weights(&files(&[
+            "pytorch_model.bin",
+            "adapter.safetensors"
+        ])));
+    }
+
+    #[test]
+    fn test_is_segmented() {
+        let req = DownloadRequest {
+            segment_size: 10,
+            connections_per_file: 4,
+            ..Default::default()
+        };
+        assert!(is_segmented(&req, 11));
+        assert!(!is_segmented(&req, 10));
+        let single = DownloadRequest {
+            connections_per_file: 1,
+            ..req.clone()
+        };
+        assert!(!is_segmented(&single, 11));
+        let never = DownloadRequest {
+            segment_size: 0,
+            ..req
+        };
+        assert!(!is_segmented(&never, 11));
+    }
+
+    #[test]
+    fn test_check_free_space() {
+        let dir = std::env::temp_dir();
+        assert!(check_free_space(&dir, 0).is_ok());
+        assert!(matches!(
+            check_free_space(&dir, u64::MAX),
+            Err(Error::InsufficientSpace {
+                needed: u64::MAX,
+                ..
+            })
+        ));
+    }
 }
@@ -0,0 +1,323 @@
+//! Typed views of the hub's model info (`/api/models/{repo}` and the search
+//! results of `/api/models`).
+//!
+//! Only the commonly used fields are spelled out; everything else the hub
+//! sends is kept in each struct's `extra` map, so nothing is lost when a
+//! value is serialized back to JSON.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// A model repository as the hub describes it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    /// The repository id (e.g. `openai-community/gpt2`).
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    /// The repository id again, under its older name; some responses (e.g.
+    /// search results) carry only this one. See [`ModelInfo::repo_id`].
+    #[serde(rename = "modelId", default, skip_serializing_if = "Option::is_none")]
+    pub model_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// The commit the revision resolved to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha: Option<String>,
+    /// When the repository was last modified (RFC 3339).
+    #[serde(
+        rename = "lastModified",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub last_modified: Option<String>,
+    /// When the repository was created (RFC 3339).
+    #[serde(rename = "createdAt", default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Gated::is_default")]
+    pub gated: Gated,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downloads: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub likes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline_tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub library_name: Option<String>,
+    /// The YAML front matter of the model card.
+    #[serde(rename = "cardData", default, skip_serializing_if = "Option::is_none")]
+    pub card_data: Option<CardData>,
+    #[serde(
+        rename = "transformersInfo",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub transformers_info: Option<TransformersInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safetensors: Option<SafetensorsInfo>,
+    /// The repository's files.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub siblings: Vec<Sibling>,
+    /// Every other field the hub sent.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl ModelInfo {
+    /// The repository id, from `id` or else `modelId`.
+    pub fn repo_id(&self) -> &str {
+        match &self.model_id {
+            Some(model_id) if self.id.is_empty() => model_id,
+            _ => &self.id,
+        }
+    }
+}
+
+/// Whether (and how) access to a repository is gated.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Gated {
+    /// Anyone can download the files (the hub's `false`).
+    #[default]
+    No,
+    /// Access is granted automatically once the terms are accepted.
+    Auto,
+    /// The authors approve each request by hand.
+    Manual,
+    /// A value this version doesn't know, kept as the hub sent it (and taken
+    /// to mean gated).
+    Other(Value),
+}
+
+impl Gated {
+    pub fn is_gated(&self) -> bool {
+        *self != Self::No
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl<'de> Deserialize<'de> for Gated {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::Null | Value::Bool(false) => Ok(Self::No),
+            Value::String(mode) if mode == "manual" => Ok(Self::Manual),
+            Value::String(mode) if mode == "auto" => Ok(Self::Auto),
+            Value::Bool(true) => Ok(Self::Auto),
+            other => Ok(Self::Other(other)),
+        }
+    }
+}
+
+impl Serialize for Gated {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::No => serializer.serialize_bool(false),
+            Self::Auto => serializer.serialize_str("auto"),
+            Self::Manual => serializer.serialize_str("manual"),
+            Self::Other(value) => value.serialize(serializer),
+        }
+    }
+}
+
+/// One file of a repository. `size`, `blob_id` and `lfs` are only present
+/// when the info was requested with `?blobs=true`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Sibling {
+    /// The file's path within the repository.
+    pub rfilename: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// The git blob id (sha1) of the file (of its LFS pointer, for an LFS
+    /// file).
+    #[serde(rename = "blobId", default, skip_serializing_if = "Option::is_none")]
+    pub blob_id: Option<String>,
+    /// Present for files stored in LFS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lfs: Option<LfsInfo>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The LFS details of a [`Sibling`] or a [`super::tree::TreeEntry`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LfsInfo {
+    /// The sha256 of the file's contents (the tree API calls it `oid`).
+    #[serde(alias = "oid")]
+    pub sha256: String,
+    pub size: u64,
+    #[serde(
+        rename = "pointerSize",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub pointer_size: Option<u64>,
+}
+
+/// The model card's metadata. Fields the card may give as either a single
+/// value or a list are always lists here.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CardData {
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub license: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub language: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub tags: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub datasets: Vec<String>,
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub base_model: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline_tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub library_name: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// How `transformers` loads the model.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TransformersInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline_tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub processor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_class: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Parameter counts of the model's safetensors weights.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SafetensorsInfo {
+    /// Parameter count by dtype (e.g. `BF16`).
+    #[serde(default)]
+    pub parameters: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub total: u64,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// A string, or a list of them (anything else is dropped).
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(one) => vec![one],
+        Value::Array(many) => many
+            .into_iter()
+            .filter_map(|v| v.as_str().map(String::from))
+            .collect(),
+        _ => vec![],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_info() {
+        let info: ModelInfo = serde_json::from_str(
+            r#"{
+                "_id": "621ffdc036468d709f17434d",
+                "id": "openai-community/gpt2",
+                "modelId": "openai-community/gpt2",
+                "sha": "607a30d783dfa663caf39e06633721c8d4cfcd7e",
+                "lastModified": "2024-02-19T10:57:45.000Z",
+                "gated": false,
+                "pipeline_tag": "text-generation",
+                "library_name": "transformers",
+                "tags": ["transformers", "gpt2"],
+                "cardData": {"license": "mit", "language": ["en"]},
+                "transformersInfo": {"auto_model": "AutoModelForCausalLM"},
+                "safetensors": {"parameters": {"F32": 137022720}, "total": 137022720},
+                "siblings": [
+                    {"rfilename": "config.json", "size": 665, "blobId": "10c66461"},
+                    {"rfilename": "model.safetensors", "size": 548105171,
+                     "blobId": "aa", "lfs": {"sha256": "bb", "size": 548105171, "pointerSize": 135}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(info.repo_id(), "openai-community/gpt2");
+        assert_eq!(info.gated, Gated::No);
+        assert_eq!(info.pipeline_tag.as_deref(), Some("text-generation"));
+        let card = info.card_data.as_ref().unwrap();
+        assert_eq!(card.license, vec!["mit"]);
+        assert_eq!(card.language, vec!["en"]);
+        assert_eq!(
+            info.transformers_info
+                .as_ref()
+                .unwrap()
+                .auto_model
+                .as_deref(),
+            Some("AutoModelForCausalLM")
+        );
+        assert_eq!(info.safetensors.as_ref().unwrap().total, 137022720);
+        assert_eq!(info.siblings[1].lfs.as_ref().unwrap().sha256, "bb");
+        assert_eq!(info.extra["_id"], "621ffdc036468d709f17434d");
+    }
+
+    #[test]
+    fn test_model_info_round_trips_unknown_fields() {
+        let json = serde_json::json!({"id": "a/b", "gated": "manual", "spaces": ["x"]});
+        let info: ModelInfo = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(info.gated, Gated::Manual);
+        assert_eq!(serde_json::to_value(&info).unwrap(), json);
+    }
+
+    #[test]
+    fn test_model_info_omits_what_the_hub_did_not_send() {
+        let json = serde_json::json!({"modelId": "a/b", "sha": "abc123"});
+        let info: ModelInfo = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&info).unwrap(), json);
+    }
+
+    #[test]
+    fn test_unknown_gated_values_are_kept() {
+        for gated in [serde_json::json!("sometimes"), serde_json::json!(2)] {
+            let json = serde_json::json!({"id": "a/b", "gated": gated});
+            let info: ModelInfo = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(info.gated, Gated::Other(gated));
+            assert!(info.gated.is_gated());
+            assert_eq!(serde_json::to_value(&info).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn test_repo_id_falls_back_to_model_id() {
+        let info: ModelInfo = serde_json::from_str(r#"{"modelId": "a/b"}"#).unwrap();
+        assert_eq!(info.repo_id(), "a/b");
+    }
+}
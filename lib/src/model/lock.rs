@@ -0,0 +1,271 @@
+//! `possum.lock`: a repository pinned to a commit and an exact list of
+//! files, so the same bytes can be fetched again later (see
+//! [`super::download::DownloadRequest::lock`]).
+//!
+//! ```toml
+//! version = 1
+//! repository = "openai-community/gpt2"
+//! revision = "main"
+//! commit = "607a30d783dfa663caf39e06633721c8d4cfcd7e"
+//!
+//! [[files]]
+//! path = "config.json"
+//! size = 665
+//! blob_id = "10c66461e4c109db5a2196bff4bb59be30396ed8"
+//!
+//! [[files]]
+//! path = "model.safetensors"
+//! size = 548105171
+//! sha256 = "248dfc3911869ec493c76e65bf2fcf7f615828b0254c12b473182f0f81d3a707"
+//! ```
+
+use super::download::DownloadRequest;
+use super::info::ModelInfo;
+use super::plan::{plan_selection, DownloadPlan};
+use super::verify::{check, ExpectedDigest};
+use crate::{Error, HubClient, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// The lockfile format version written by [`Lockfile::from_plan`].
+pub const LOCK_VERSION: u32 = 1;
+
+/// The default lockfile name.
+pub const LOCK_FILE: &str = "possum.lock";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub version: u32,
+    pub repository: String,
+    /// The revision the lock was made from (e.g. `main`).
+    pub revision: String,
+    /// The commit that revision resolved to; locked downloads fetch from it.
+    pub commit: String,
+    pub files: Vec<LockedFile>,
+}
+
+/// One locked file. LFS files carry the sha256 of their contents, others
+/// their git blob id.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedFile {
+    /// The file's path within the repository.
+    pub path: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob_id: Option<String>,
+}
+
+impl LockedFile {
+    pub fn digest(&self) -> Option<ExpectedDigest> {
+        match (&self.sha256, &self.blob_id) {
+            (Some(sha256), _) => Some(ExpectedDigest::Sha256(sha256.to_ascii_lowercase())),
+            (None, Some(blob_id)) => Some(ExpectedDigest::GitSha1(blob_id.to_ascii_lowercase())),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Lock the files `req` selects: resolve its revision to a commit and record
+/// each file's size and digest, as the hub publishes them (nothing on disk
+/// is looked at, let alone hashed).
+pub async fn lock(req: &DownloadRequest) -> Result<Lockfile> {
+    HubClient::with_endpoint(&req.api_base_url)?.lock(req).await
+}
+
+impl HubClient {
+    /// [`lock`] through this client (`req.api_base_url` is ignored).
+    pub async fn lock(&self, req: &DownloadRequest) -> Result<Lockfile> {
+        let plan = match &req.token {
+            Some(token) => plan_selection(&self.with_token(Some(token)), req).await?,
+            None => plan_selection(self, req).await?,
+        };
+        Lockfile::from_plan(&plan)
+    }
+}
+
+impl Lockfile {
+    /// A lockfile of a plan's commit and selected files. Every file needs a
+    /// size and digest, and the plan a commit.
+    pub fn from_plan(plan: &DownloadPlan) -> Result<Self> {
+        let commit = plan.commit.clone().ok_or_else(|| {
+            Error::InvalidResponse(format!(
+                "{}: the hub did not report a commit sha to lock",
+                plan.repository
+            ))
+        })?;
+        let files = plan
+            .files
+            .iter()
+            .map(|planned| {
+                let missing = |what| {
+                    Error::InvalidResponse(format!(
+                        "{}: the hub published no {what} to lock",
+                        planned.file
+                    ))
+                };
+                let size = planned.size.ok_or_else(|| missing("size"))?;
+                let (sha256, blob_id) = match planned.digest.clone() {
+                    Some(ExpectedDigest::Sha256(hex)) => (Some(hex), None),
+                    Some(ExpectedDigest::GitSha1(hex)) => (None, Some(hex)),
+                    None => return Err(missing("digest")),
+                };
+                Ok(LockedFile {
+                    path: planned.file.clone(),
+                    size,
+                    sha256,
+                    blob_id,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            version: LOCK_VERSION,
+            repository: plan.repository.clone(),
+            revision: plan.revision.clone(),
+            commit,
+            files,
+        })
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        let lock: Self = toml::from_str(toml).map_err(|e| Error::Lock(e.to_string()))?;
+        if lock.version != LOCK_VERSION {
+            return Err(Error::Lock(format!(
+                "unsupported version {} (expected {LOCK_VERSION})",
+                lock.version
+            )));
+        }
+        Ok(lock)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("a lockfile serializes")
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_toml())?;
+        Ok(())
+    }
+
+    /// Check the hub's info for the locked commit against the lock: every
+    /// locked file must still be there with the same size and digest.
+    /// Returns the locked paths.
+    pub fn check(&self, info: &ModelInfo) -> Result<Vec<String>> {
+        self.files
+            .iter()
+            .map(|locked| {
+                let sibling = info
+                    .siblings
+                    .iter()
+                    .find(|s| s.rfilename == locked.path)
+                    .ok_or_else(|| {
+                        Error::Lock(format!("{} is not in commit {}", locked.path, self.commit))
+                    })?;
+                if sibling.size.is_some_and(|size| size != locked.size) {
+                    return Err(Error::Lock(format!(
+                        "{}: size differs (locked {}, hub {})",
+                        locked.path,
+                        locked.size,
+                        sibling.size.unwrap_or_default()
+                    )));
+                }
+                if let (Some(expected), Some(actual)) =
+                    (locked.digest(), ExpectedDigest::from_sibling(sibling))
+                {
+                    check(&locked.path, &expected, actual.hex())?;
+                }
+                Ok(locked.path.clone())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::info::{LfsInfo, Sibling};
+
+    fn lockfile() -> Lockfile {
+        Lockfile {
+            version: LOCK_VERSION,
+            repository: "test/model".to_string(),
+            revision: "main".to_string(),
+            commit: "abc123".to_string(),
+            files: vec![
+                LockedFile {
+                    path: "config.json".to_string(),
+                    size: 2,
+                    sha256: None,
+                    blob_id: Some("b1".to_string()),
+                },
+                LockedFile {
+                    path: "model.safetensors".to_string(),
+                    size: 10,
+                    sha256: Some("aa".to_string()),
+                    blob_id: None,
+                },
+            ],
+        }
+    }
+
+    fn info(weights_sha256: &str) -> ModelInfo {
+        ModelInfo {
+            siblings: vec![
+                Sibling {
+                    rfilename: "config.json".to_string(),
+                    size: Some(2),
+                    blob_id: Some("b1".to_string()),
+                    ..Default::default()
+                },
+                Sibling {
+                    rfilename: "model.safetensors".to_string(),
+                    size: Some(10),
+                    blob_id: Some("b2".to_string()),
+                    lfs: Some(LfsInfo {
+                        sha256: weights_sha256.to_string(),
+                        size: 10,
+                        pointer_size: None,
+                    }),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_toml_round_trip() {
+        let lock = lockfile();
+        let toml = lock.to_toml();
+        assert!(toml.contains("[[files]]"));
+        assert_eq!(Lockfile::from_toml(&toml).unwrap(), lock);
+    }
+
+    #[test]
+    fn test_from_toml_rejects_other_versions() {
+        let toml = lockfile().to_toml().replace("version = 1", "version = 2");
+        assert!(matches!(Lockfile::from_toml(&toml), Err(Error::Lock(_))));
+    }
+
+    #[test]
+    fn test_check() {
+        let lock = lockfile();
+        assert_eq!(
+            lock.check(&info("aa")).unwrap(),
+            vec!["config.json", "model.safetensors"]
+        );
+        assert!(matches!(
+            lock.check(&info("ff")),
+            Err(Error::Integrity { .. })
+        ));
+        let mut missing = info("aa");
+        missing.siblings.pop();
+        assert!(matches!(lock.check(&missing), Err(Error::Lock(_))));
+    }
+}
@@ -0,0 +1,181 @@
+//! A manifest of models to download together (`possum sync`).
+//!
+//! ```toml
+//! # Where models without their own `to` go: <to>/<repository>[:<revision>]
+//! to = "/srv/models"
+//!
+//! [[models]]
+//! repository = "openai-community/gpt2"
+//! include = ["*.json", "*.safetensors"]
+//!
+//! [[models]]
+//! repository = "TheBloke/Llama-2-7B-Chat-GGUF"
+//! revision = "main"
+//! include = ["*Q4_K_M.gguf"]
+//! to = "/srv/llama"
+//! ```
+
+use super::download::{DownloadRequest, ProgressMode};
+use super::report::DownloadReport;
+use crate::{Error, HubClient, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// The root models without their own `to` go under, if the manifest doesn't
+/// say.
+pub const DEFAULT_ROOT: &str = "./huggingface";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The root of the destinations of entries without their own `to`.
+    #[serde(default = "default_root")]
+    pub to: PathBuf,
+    pub models: Vec<ManifestEntry>,
+}
+
+fn default_root() -> PathBuf {
+    PathBuf::from(DEFAULT_ROOT)
+}
+
+/// One model of a [`Manifest`]: the same selection as a single download.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub repository: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// The directory to download into (default: see [`Manifest::to`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<PathBuf>,
+}
+
+impl Manifest {
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        toml::from_str(toml).map_err(|e| Error::Manifest(e.to_string()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// The directory `entry` downloads into: its `to`, else
+    /// `<root>/<repository>[:<revision>]` as for `possum model download`.
+    pub fn destination(&self, entry: &ManifestEntry) -> PathBuf {
+        if let Some(to) = &entry.to {
+            return to.clone();
+        }
+        let dir = self.to.join(&entry.repository);
+        match &entry.revision {
+            Some(rev) => PathBuf::from(format!("{}:{rev}", dir.display())),
+            None => dir,
+        }
+    }
+
+    /// The download request of `entry`, otherwise like `template`.
+    pub fn request(&self, entry: &ManifestEntry, template: &DownloadRequest) -> DownloadRequest {
+        DownloadRequest {
+            repository: entry.repository.clone(),
+            revision: entry.revision.clone(),
+            to: self.destination(entry),
+            include: entry.include.clone(),
+            exclude: entry.exclude.clone(),
+            ..template.clone()
+        }
+    }
+}
+
+/// The outcome of one entry of a [`HubClient::sync`].
+#[derive(Debug)]
+pub struct SyncedModel {
+    pub entry: ManifestEntry,
+    /// Where it was downloaded to.
+    pub to: PathBuf,
+    /// The report, or why the model failed (including
+    /// [`Error::DownloadFailed`] if any of its files did).
+    pub result: Result<DownloadReport>,
+}
+
+impl HubClient {
+    /// Download every model of `manifest` at once, with at most
+    /// `template.concurrency` files in flight across all of them. Each
+    /// entry's request is `template` with the entry's selection and
+    /// destination; progress bars are hidden. Every entry runs to completion,
+    /// so one failing doesn't stop the others.
+    pub async fn sync(&self, manifest: &Manifest, template: &DownloadRequest) -> Vec<SyncedModel> {
+        let concurrency = template.concurrency.max(1);
+        let template = DownloadRequest {
+            concurrency,
+            progress: ProgressMode::Hidden,
+            file_permits: Some(Arc::new(Semaphore::new(concurrency))),
+            ..template.clone()
+        };
+        futures::future::join_all(manifest.models.iter().map(|entry| {
+            let req = manifest.request(entry, &template);
+            async move {
+                let result = self
+                    .download(&req)
+                    .await
+                    .and_then(DownloadReport::into_result);
+                SyncedModel {
+                    entry: entry.clone(),
+                    to: req.to,
+                    result,
+                }
+            }
+        }))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let manifest = Manifest::from_toml(
+            r#"
+            [[models]]
+            repository = "openai-community/gpt2"
+            include = ["*.json"]
+
+            [[models]]
+            repository = "test/model"
+            revision = "v1"
+
+            [[models]]
+            repository = "test/other"
+            to = "/srv/other"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(manifest.to, Path::new(DEFAULT_ROOT));
+        assert_eq!(manifest.models[0].include, vec!["*.json"]);
+        assert_eq!(
+            manifest.destination(&manifest.models[0]),
+            Path::new("./huggingface/openai-community/gpt2")
+        );
+        assert_eq!(
+            manifest.destination(&manifest.models[1]),
+            Path::new("./huggingface/test/model:v1")
+        );
+        assert_eq!(
+            manifest.destination(&manifest.models[2]),
+            Path::new("/srv/other")
+        );
+    }
+
+    #[test]
+    fn test_manifest_rejects_unknown_shape() {
+        assert!(matches!(
+            Manifest::from_toml("[[models]]\nrevision = \"main\"\n"),
+            Err(Error::Manifest(_))
+        ));
+    }
+}
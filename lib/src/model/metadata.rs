@@ -1,20 +1,28 @@
-use crate::BoxError;
-use reqwest::Client;
-use serde_json::Value;
-
-/// Fetch a repository's metadata as raw JSON.
-pub async fn metadata(repository: &str, api_base_url: &str) -> Result<Value, BoxError> {
-    let client = Client::new();
-    let url = build_metadata_url(repository, api_base_url);
-    let response = client.get(&url).send().await?;
-    if response.status().is_success() {
-        Ok(response.json().await?)
-    } else {
-        Err(format!(
-            "Failed to get metadata for {repository} (HTTP {})",
-            response.status()
-        )
-        .into())
+use super::info::ModelInfo;
+use crate::{Error, HubClient, Result};
+
+/// Fetch a repository's metadata.
+pub async fn metadata(
+    repository: &str,
+    token: Option<&str>,
+    api_base_url: &str,
+) -> Result<ModelInfo> {
+    HubClient::with_endpoint(api_base_url)?
+        .with_token(token)
+        .metadata(repository)
+        .await
+}
+
+impl HubClient {
+    /// Fetch a repository's metadata.
+    pub async fn metadata(&self, repository: &str) -> Result<ModelInfo> {
+        let url = build_metadata_url(repository, self.endpoint());
+        let response = self.send(&url).await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            Err(Error::from_response(&url, &response))
+        }
     }
 }
 
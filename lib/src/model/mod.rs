@@ -1,11 +1,39 @@
 //! Operations on 🤗 model repositories: download, metadata, revisions, search.
 
+pub mod bandwidth;
+pub mod cache;
 pub mod download;
+pub mod info;
+pub mod lock;
+pub mod manifest;
 pub mod metadata;
+mod offline;
+pub mod plan;
+pub mod progress;
+pub mod report;
+pub mod retry;
 pub mod revisions;
 pub mod search;
+mod segmented;
+pub mod sidecar;
+pub mod tree;
+pub mod verify;
 
-pub use download::{download, list_files, select_files, DownloadRequest, ProgressMode};
+pub use bandwidth::BandwidthLimiter;
+pub use download::{
+    download, list_download_files, list_files, select_files, DownloadRequest, ProgressMode,
+    StorageLayout,
+};
+pub use info::{CardData, Gated, LfsInfo, ModelInfo, SafetensorsInfo, Sibling, TransformersInfo};
+pub use lock::{lock, LockedFile, Lockfile};
+pub use manifest::{Manifest, ManifestEntry, SyncedModel};
 pub use metadata::metadata;
+pub use plan::{plan_download, DownloadPlan, LocalState, PlannedFile};
+pub use progress::{Bars, ProgressEvent, ProgressSink};
+pub use report::{DownloadReport, FileOutcome, FileReport};
+pub use retry::RetryPolicy;
 pub use revisions::revisions;
 pub use search::search;
+pub use tokio_util::sync::CancellationToken;
+pub use tree::{list_tree, tree_of, EntryKind, TreeEntry};
+pub use verify::{ExpectedDigest, Verification};
@@ -0,0 +1,229 @@
+//! Offline mode (see [`super::DownloadRequest::offline`]): a revision's files
+//! are listed from what earlier downloads left on disk instead of by the hub.
+//!
+//! - With a lock, the lock itself lists the files.
+//! - In a flat directory, the [`Sidecar`] records the listing of the commit
+//!   it was downloaded from (or, if written before listings were recorded,
+//!   at least the files downloaded).
+//! - In the hub cache, `refs/<revision>` names the commit, and the files
+//!   linked into its snapshot are listed.
+
+use super::cache::RepoCache;
+use super::download::{DownloadRequest, StorageLayout};
+use super::info::{LfsInfo, ModelInfo, Sibling};
+use super::sidecar::Sidecar;
+use super::verify::ExpectedDigest;
+use crate::{Error, Result};
+use std::fs;
+use std::path::Path;
+
+/// The model info of `req`'s revision (or lock) as far as local state knows
+/// it: its commit and its files, each with the size and digest recorded.
+pub(super) fn local_model_info(req: &DownloadRequest) -> Result<ModelInfo> {
+    if let Some(lock) = &req.lock {
+        let siblings = lock
+            .files
+            .iter()
+            .map(|locked| sibling(&locked.path, locked.size, locked.digest()))
+            .collect();
+        return Ok(model_info(Some(lock.commit.clone()), siblings));
+    }
+
+    let revision = req.revision.as_deref().unwrap_or("main");
+    let unavailable = || Error::NotAvailableOffline {
+        repository: req.repository.clone(),
+        revision: revision.to_string(),
+        missing: vec![],
+    };
+    match req.layout {
+        StorageLayout::Flat => {
+            let sidecar = Sidecar::load_for(&req.to, &req.repository);
+            let recorded = [&sidecar.revision, &sidecar.commit]
+                .into_iter()
+                .any(|r| r.as_deref() == Some(revision));
+            if !recorded {
+                return Err(unavailable());
+            }
+            let siblings = if sidecar.listing.is_empty() {
+                sidecar
+                    .files
+                    .iter()
+                    .map(|(file, record)| {
+                        let digest = record.digest.as_deref().and_then(ExpectedDigest::from_etag);
+                        sibling(file, record.size, digest)
+                    })
+                    .collect()
+            } else {
+                sidecar.listing
+            };
+            Ok(model_info(sidecar.commit, siblings))
+        }
+        StorageLayout::HfCache => {
+            let cache = RepoCache::new(&req.to, &req.repository);
+            let commit = match fs::read_to_string(cache.ref_path(revision)) {
+                Ok(commit) => commit.trim().to_string(),
+                Err(_) => revision.to_string(),
+            };
+            let snapshot = cache.snapshot_dir(&commit);
+            if !snapshot.is_dir() {
+                return Err(unavailable());
+            }
+            let mut siblings = vec![];
+            list_snapshot(&snapshot, "", &mut siblings)?;
+            siblings.sort_by(|a, b| a.rfilename.cmp(&b.rfilename));
+            Ok(model_info(Some(commit), siblings))
+        }
+    }
+}
+
+// Add the files under `dir` (at `prefix` within the snapshot) to `siblings`,
+// each with the size of its blob and the digest the blob is named by.
+fn list_snapshot(dir: &Path, prefix: &str, siblings: &mut Vec<Sibling>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let file = format!("{prefix}{name}");
+        let path = entry.path();
+        if path.is_dir() {
+            list_snapshot(&path, &format!("{file}/"), siblings)?;
+            continue;
+        }
+        // A dangling link (its blob deleted) is as good as missing.
+        let Ok(meta) = fs::metadata(&path) else {
+            continue;
+        };
+        let digest = fs::read_link(&path)
+            .ok()
+            .and_then(|target| {
+                target
+                    .file_name()
+                    .map(|etag| etag.to_string_lossy().into_owned())
+            })
+            .and_then(|etag| ExpectedDigest::from_etag(&etag));
+        siblings.push(sibling(&file, meta.len(), digest));
+    }
+    Ok(())
+}
+
+fn sibling(file: &str, size: u64, digest: Option<ExpectedDigest>) -> Sibling {
+    let (blob_id, lfs) = match digest {
+        Some(ExpectedDigest::Sha256(sha256)) => (
+            None,
+            Some(LfsInfo {
+                sha256,
+                size,
+                pointer_size: None,
+            }),
+        ),
+        Some(ExpectedDigest::GitSha1(blob_id)) => (Some(blob_id), None),
+        None => (None, None),
+    };
+    Sibling {
+        rfilename: file.to_string(),
+        size: Some(size),
+        blob_id,
+        lfs,
+        ..Default::default()
+    }
+}
+
+fn model_info(sha: Option<String>, siblings: Vec<Sibling>) -> ModelInfo {
+    ModelInfo {
+        sha,
+        siblings,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::sidecar::FileRecord;
+
+    #[test]
+    fn test_local_model_info_from_sidecar() {
+        let dir = tempfile::tempdir().unwrap();
+        let req = DownloadRequest {
+            repository: "test/model".to_string(),
+            to: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            local_model_info(&req),
+            Err(Error::NotAvailableOffline { .. })
+        ));
+
+        let mut sidecar = Sidecar {
+            repository: Some("test/model".to_string()),
+            revision: Some("main".to_string()),
+            commit: Some("abc123".to_string()),
+            ..Default::default()
+        };
+        sidecar.files.insert(
+            "config.json".to_string(),
+            FileRecord {
+                size: 2,
+                digest: Some("9e26dfeeb6e641a33dae4961196235bdb965b21b".to_string()),
+                modified: None,
+            },
+        );
+        sidecar.save(dir.path()).unwrap();
+        let info = local_model_info(&req).unwrap();
+        assert_eq!(info.sha.as_deref(), Some("abc123"));
+        assert_eq!(info.siblings[0].rfilename, "config.json");
+        assert_eq!(
+            info.siblings[0].blob_id.as_deref(),
+            Some("9e26dfeeb6e641a33dae4961196235bdb965b21b")
+        );
+
+        // A recorded listing takes precedence over the downloaded files.
+        sidecar.listing = vec![sibling("config.json", 2, None), sibling("b.bin", 9, None)];
+        sidecar.save(dir.path()).unwrap();
+        assert_eq!(local_model_info(&req).unwrap().siblings.len(), 2);
+
+        let other = DownloadRequest {
+            revision: Some("v2".to_string()),
+            ..req.clone()
+        };
+        assert!(matches!(
+            local_model_info(&other),
+            Err(Error::NotAvailableOffline { .. })
+        ));
+        // Nor is another repository's sidecar any use.
+        let other = DownloadRequest {
+            repository: "other/model".to_string(),
+            ..req
+        };
+        assert!(matches!(
+            local_model_info(&other),
+            Err(Error::NotAvailableOffline { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_local_model_info_from_hf_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = RepoCache::new(dir.path(), "test/model");
+        let etag = "9e26dfeeb6e641a33dae4961196235bdb965b21b";
+        fs::create_dir_all(cache.blob_path(etag).parent().unwrap()).unwrap();
+        fs::write(cache.blob_path(etag), "{}").unwrap();
+        cache.write_ref("main", "abc123").unwrap();
+        cache
+            .link_snapshot("abc123", "nested/config.json", etag)
+            .unwrap();
+
+        let req = DownloadRequest {
+            repository: "test/model".to_string(),
+            to: dir.path().to_path_buf(),
+            layout: StorageLayout::HfCache,
+            ..Default::default()
+        };
+        let info = local_model_info(&req).unwrap();
+        assert_eq!(info.sha.as_deref(), Some("abc123"));
+        assert_eq!(info.siblings.len(), 1);
+        assert_eq!(info.siblings[0].rfilename, "nested/config.json");
+        assert_eq!(info.siblings[0].size, Some(2));
+        assert_eq!(info.siblings[0].blob_id.as_deref(), Some(etag));
+    }
+}
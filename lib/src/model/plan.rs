@@ -0,0 +1,452 @@
+use super::cache::RepoCache;
+use super::download::{
+    has_safetensors_weights, hash_file, partial_len, select_files, DownloadRequest, StorageLayout,
+};
+use super::info::{ModelInfo, Sibling};
+use super::offline::local_model_info;
+use super::sidecar::{FileRecord, Sidecar};
+use super::verify::{ContentHasher, ExpectedDigest};
+use crate::{Error, HubClient, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path};
+
+/// What [`super::download`] would do for a request, worked out without
+/// downloading (or writing) anything.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DownloadPlan {
+    pub repository: String,
+    /// The revision asked for (`main` if none was).
+    pub revision: String,
+    /// The commit the revision currently resolves to, if the hub said.
+    pub commit: Option<String>,
+    /// The selected files, in repository order.
+    pub files: Vec<PlannedFile>,
+    /// Files the include/exclude globs selected but the safetensors
+    /// heuristic drops (`*.bin`/`*.pt` beside `model*.safetensors`).
+    pub skipped: Vec<String>,
+    /// With [`DownloadRequest::prune`], the tracked files an earlier
+    /// download put in the directory that are no longer selected, which a
+    /// successful download deletes.
+    pub prune: Vec<String>,
+    /// Every file of the commit as listed (by the hub, or offline from local
+    /// state), selected or not.
+    #[serde(skip)]
+    pub listing: Vec<Sibling>,
+}
+
+impl DownloadPlan {
+    /// The bytes still to be fetched over all the selected files.
+    pub fn bytes_to_fetch(&self) -> u64 {
+        self.files.iter().map(PlannedFile::bytes_to_fetch).sum()
+    }
+}
+
+/// One selected file of a [`DownloadPlan`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlannedFile {
+    /// The file's path within the repository.
+    pub file: String,
+    /// Its size on the hub, if known.
+    pub size: Option<u64>,
+    /// The digest it will be verified against.
+    pub digest: Option<ExpectedDigest>,
+    /// What is already on disk for it.
+    pub local: LocalState,
+}
+
+impl PlannedFile {
+    /// The bytes still to be fetched for this file.
+    pub fn bytes_to_fetch(&self) -> u64 {
+        let size = self.size.unwrap_or(0);
+        match self.local {
+            LocalState::UpToDate => 0,
+            LocalState::Partial { bytes } => size.saturating_sub(bytes),
+            LocalState::Missing | LocalState::Stale => size,
+        }
+    }
+}
+
+/// What is already on disk for a file, in the request's layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum LocalState {
+    /// Nothing yet.
+    Missing,
+    /// An interrupted download left `bytes` in its `.incomplete` temporary.
+    Partial { bytes: u64 },
+    /// Present and matching the hub: in the hub cache, its blob is there; in
+    /// a flat directory, the file has the hub's size and digest (checked
+    /// against the directory's [`Sidecar`], or else by hashing it).
+    UpToDate,
+    /// A different file (by size or digest) is in the way and will be
+    /// replaced.
+    Stale,
+}
+
+/// Work out what [`super::download`] would do for `req`: the commit, the
+/// selected files and their sizes and local state, and the files the
+/// safetensors heuristic skips. With [`DownloadRequest::offline`], the
+/// commit and files come from local state instead of the hub.
+pub async fn plan_download(req: &DownloadRequest) -> Result<DownloadPlan> {
+    HubClient::with_endpoint(&req.api_base_url)?
+        .plan_download(req)
+        .await
+}
+
+impl HubClient {
+    /// [`plan_download`] through this client (`req.api_base_url` is
+    /// ignored, as by [`HubClient::download`]).
+    pub async fn plan_download(&self, req: &DownloadRequest) -> Result<DownloadPlan> {
+        match &req.token {
+            Some(token) => plan(&self.with_token(Some(token)), req).await,
+            None => plan(self, req).await,
+        }
+    }
+}
+
+pub(super) async fn plan(hub: &HubClient, req: &DownloadRequest) -> Result<DownloadPlan> {
+    plan_with(hub, req, true).await
+}
+
+// The plan of `req` without looking at the disk: every file is `Missing` and
+// nothing is pruned (for a lockfile, which only needs the selection).
+pub(super) async fn plan_selection(hub: &HubClient, req: &DownloadRequest) -> Result<DownloadPlan> {
+    plan_with(hub, req, false).await
+}
+
+async fn plan_with(
+    hub: &HubClient,
+    req: &DownloadRequest,
+    check_local: bool,
+) -> Result<DownloadPlan> {
+    if let Some(lock) = &req.lock {
+        if lock.repository != req.repository {
+            return Err(Error::Lock(format!(
+                "it locks {}, not {}",
+                lock.repository, req.repository
+            )));
+        }
+    }
+    let model_info = request_model_info(hub, req).await?;
+
+    let (selected, skipped) = match &req.lock {
+        Some(lock) => (lock.check(&model_info)?, vec![]),
+        None => {
+            let names: Vec<String> = model_info
+                .siblings
+                .iter()
+                .map(|s| s.rfilename.clone())
+                .collect();
+            let selected = select_files(
+                names.clone(),
+                &req.include,
+                &req.exclude,
+                has_safetensors_weights(&names),
+            )?;
+            let skipped = select_files(names, &req.include, &req.exclude, false)?
+                .into_iter()
+                .filter(|file| !selected.contains(file))
+                .collect();
+            (selected, skipped)
+        }
+    };
+
+    let sidecar = match req.layout {
+        StorageLayout::Flat if check_local => Sidecar::load_for(&req.to, &req.repository),
+        _ => Sidecar::default(),
+    };
+    // Each selected file with the size and digest it must have: a lock's
+    // own, whatever the hub publishes (or leaves out), else the hub's.
+    let wanted: Vec<(String, Option<u64>, Option<ExpectedDigest>)> = match &req.lock {
+        Some(lock) => lock
+            .files
+            .iter()
+            .map(|locked| (locked.path.clone(), Some(locked.size), locked.digest()))
+            .collect(),
+        None => model_info
+            .siblings
+            .iter()
+            .filter(|sibling| selected.contains(&sibling.rfilename))
+            .map(|sibling| {
+                let digest = ExpectedDigest::from_sibling(sibling);
+                (sibling.rfilename.clone(), sibling.size, digest)
+            })
+            .collect(),
+    };
+
+    // Offline nothing can be fetched again, so `force` is ignored there.
+    let force = req.force && !req.offline;
+    let cache = RepoCache::new(&req.to, &req.repository);
+    let mut files = Vec::with_capacity(wanted.len());
+    for (file, size, digest) in wanted {
+        let local = match (req.layout, &digest) {
+            _ if !check_local => LocalState::Missing,
+            (StorageLayout::Flat, _) => {
+                let path = req.to.join(&file);
+                let record = sidecar.files.get(&file);
+                flat_state(&path, size, digest.as_ref(), record, force).await
+            }
+            (StorageLayout::HfCache, Some(digest)) => cache_state(&cache.blob_path(digest.hex())),
+            (StorageLayout::HfCache, None) => LocalState::Missing,
+        };
+        files.push(PlannedFile {
+            file,
+            size,
+            digest,
+            local,
+        });
+    }
+
+    let (revision, commit) = match &req.lock {
+        Some(lock) => (lock.revision.clone(), Some(lock.commit.clone())),
+        None => (
+            req.revision.clone().unwrap_or_else(|| "main".to_string()),
+            model_info.sha,
+        ),
+    };
+    let prune = match (req.prune, req.layout) {
+        (true, StorageLayout::Flat) => sidecar
+            .files
+            .keys()
+            .filter(|file| !selected.contains(file) && is_plain_relative(file))
+            .cloned()
+            .collect(),
+        _ => vec![],
+    };
+
+    Ok(DownloadPlan {
+        repository: req.repository.clone(),
+        revision,
+        commit,
+        files,
+        skipped,
+        prune,
+        listing: model_info.siblings,
+    })
+}
+
+// The model info `req` selects from: that of its lock's commit (or its
+// revision) as the hub lists it, or offline, as local state records it.
+pub(super) async fn request_model_info(
+    hub: &HubClient,
+    req: &DownloadRequest,
+) -> Result<ModelInfo> {
+    if req.offline {
+        return local_model_info(req);
+    }
+    let revision = match &req.lock {
+        Some(lock) => Some(lock.commit.as_str()),
+        None => req.revision.as_deref(),
+    };
+    hub.model_info(&req.repository, revision).await
+}
+
+// Whether `file` names something inside the directory it's relative to (a
+// sidecar is only a file on disk, so it isn't trusted to stay in bounds).
+fn is_plain_relative(file: &str) -> bool {
+    Path::new(file)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+}
+
+// A blob in the hub cache is named by its digest, so it's up to date if
+// it's there at all.
+fn cache_state(blob: &Path) -> LocalState {
+    if blob.exists() {
+        return LocalState::UpToDate;
+    }
+    match partial_len(blob) {
+        0 => LocalState::Missing,
+        bytes => LocalState::Partial { bytes },
+    }
+}
+
+// A file in a flat directory is up to date if it has the hub's size and
+// digest. The sidecar's `record` vouches for the digest of a file that hasn't
+// changed since it was downloaded; any other file is hashed. Without a
+// digest to check, a matching size has to do. With `force` every file is
+// fetched again anyway, so one that's there is `Stale` without checking.
+async fn flat_state(
+    path: &Path,
+    size: Option<u64>,
+    digest: Option<&ExpectedDigest>,
+    record: Option<&FileRecord>,
+    force: bool,
+) -> LocalState {
+    let Ok(meta) = fs::metadata(path) else {
+        return match partial_len(path) {
+            0 => LocalState::Missing,
+            bytes => LocalState::Partial { bytes },
+        };
+    };
+    if force {
+        return LocalState::Stale;
+    }
+    if size.is_some_and(|size| size != meta.len()) {
+        return LocalState::Stale;
+    }
+    match digest {
+        Some(digest) if record.is_some_and(|r| r.matches(&meta, digest.hex())) => {
+            LocalState::UpToDate
+        }
+        Some(digest) => {
+            let mut hasher = ContentHasher::new(digest, meta.len());
+            match hash_file(path, &mut hasher).await {
+                Ok(()) if hasher.finalize_hex() == digest.hex() => LocalState::UpToDate,
+                _ => LocalState::Stale,
+            }
+        }
+        None if size.is_some() => LocalState::UpToDate,
+        None => LocalState::Stale,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn planned(size: u64, local: LocalState) -> PlannedFile {
+        PlannedFile {
+            file: "a".to_string(),
+            size: Some(size),
+            digest: None,
+            local,
+        }
+    }
+
+    #[test]
+    fn test_bytes_to_fetch() {
+        assert_eq!(planned(10, LocalState::Missing).bytes_to_fetch(), 10);
+        assert_eq!(planned(10, LocalState::Stale).bytes_to_fetch(), 10);
+        assert_eq!(planned(10, LocalState::UpToDate).bytes_to_fetch(), 0);
+        assert_eq!(
+            planned(10, LocalState::Partial { bytes: 4 }).bytes_to_fetch(),
+            6
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flat_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let done = dir.path().join("done.json");
+        let half = dir.path().join("half.bin");
+        fs::write(&done, "{}").unwrap();
+        fs::write(dir.path().join("half.bin.incomplete"), "abc").unwrap();
+
+        assert_eq!(
+            flat_state(&done, Some(2), None, None, false).await,
+            LocalState::UpToDate
+        );
+        assert_eq!(
+            flat_state(&done, Some(3), None, None, false).await,
+            LocalState::Stale
+        );
+        assert_eq!(
+            flat_state(&half, Some(9), None, None, false).await,
+            LocalState::Partial { bytes: 3 }
+        );
+        assert_eq!(
+            flat_state(&dir.path().join("none.bin"), Some(9), None, None, false).await,
+            LocalState::Missing
+        );
+        // Forced, what's there isn't checked: it's all fetched again.
+        assert_eq!(
+            flat_state(&done, Some(2), None, None, true).await,
+            LocalState::Stale
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flat_state_counts_fetched_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.safetensors");
+        // A segmented temporary is pre-allocated to the full size; only the
+        // segments its state records are fetched.
+        fs::File::create(dir.path().join("model.safetensors.incomplete"))
+            .unwrap()
+            .set_len(100)
+            .unwrap();
+        fs::write(
+            dir.path().join("model.safetensors.incomplete.json"),
+            r#"{"etag": null, "size": 100, "segment_size": 10, "segments": [[0, 9], [30, 39]]}"#,
+        )
+        .unwrap();
+
+        let local = flat_state(&path, Some(100), None, None, false).await;
+        assert_eq!(local, LocalState::Partial { bytes: 20 });
+        assert_eq!(planned(100, local).bytes_to_fetch(), 80);
+    }
+
+    #[tokio::test]
+    async fn test_flat_state_checks_digest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        fs::write(&path, "{}").unwrap();
+        // `printf '{}' | git hash-object --stdin`
+        let right = ExpectedDigest::GitSha1("9e26dfeeb6e641a33dae4961196235bdb965b21b".to_string());
+        let wrong = ExpectedDigest::GitSha1("0".repeat(40));
+
+        assert_eq!(
+            flat_state(&path, Some(2), Some(&right), None, false).await,
+            LocalState::UpToDate
+        );
+        assert_eq!(
+            flat_state(&path, Some(2), Some(&wrong), None, false).await,
+            LocalState::Stale
+        );
+        // A matching record is trusted without hashing.
+        let record = FileRecord::of(&path, Some(wrong.hex())).unwrap();
+        assert_eq!(
+            flat_state(&path, Some(2), Some(&wrong), Some(&record), false).await,
+            LocalState::UpToDate
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plan_selection_ignores_disk() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/models/test/model"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "sha": "abc123",
+                "siblings": [{"rfilename": "config.json", "size": 2,
+                              "blobId": "9e26dfeeb6e641a33dae4961196235bdb965b21b"}]
+            })))
+            .mount(&server)
+            .await;
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("config.json"), "{}").unwrap();
+        let hub = HubClient::with_endpoint(&server.uri()).unwrap();
+        let req = DownloadRequest {
+            repository: "test/model".to_string(),
+            to: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let local = |plan: DownloadPlan| plan.files[0].local;
+        assert_eq!(local(plan(&hub, &req).await.unwrap()), LocalState::UpToDate);
+        assert_eq!(
+            local(plan_selection(&hub, &req).await.unwrap()),
+            LocalState::Missing
+        );
+    }
+
+    #[test]
+    fn test_is_plain_relative() {
+        assert!(is_plain_relative("config.json"));
+        assert!(is_plain_relative("onnx/model.onnx"));
+        assert!(!is_plain_relative("../elsewhere"));
+        assert!(!is_plain_relative("/etc/passwd"));
+    }
+
+    #[test]
+    fn test_plan_serializes_local_state() {
+        let json = serde_json::to_value(planned(10, LocalState::Partial { bytes: 4 })).unwrap();
+        assert_eq!(json["local"]["state"], "partial");
+        assert_eq!(json["local"]["bytes"], 4);
+    }
+}
@@ -0,0 +1,330 @@
+//! Reporting a download's progress: a [`ProgressSink`] receives
+//! [`ProgressEvent`]s as the download goes; [`Bars`] renders them as
+//! `indicatif` progress bars.
+
+use super::plan::DownloadPlan;
+use super::report::DownloadReport;
+use super::verify::Verification;
+use crate::Error;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Something that happened during a [`super::download`].
+#[derive(Debug)]
+pub enum ProgressEvent<'a> {
+    /// The revision is resolved, the files to fetch are known and the
+    /// download is starting; [`ProgressEvent::Finished`] always follows.
+    Planned { plan: &'a DownloadPlan },
+    /// A file's transfer (re)started: `size` bytes in all (`0` if unknown),
+    /// `done` of them already on disk from an earlier attempt.
+    FileStarted { file: &'a str, size: u64, done: u64 },
+    /// `bytes` more bytes of a file arrived.
+    Advanced { file: &'a str, bytes: u64 },
+    /// Attempt `attempt` at a file failed with a transient `error`; the
+    /// next starts after `delay`.
+    Retrying {
+        file: &'a str,
+        attempt: u32,
+        delay: Duration,
+        error: &'a Error,
+    },
+    /// A file is in place, fetched or (for [`Verification::Cached`] and
+    /// [`Verification::UpToDate`]) found already there.
+    FileFinished {
+        file: &'a str,
+        verification: Verification,
+    },
+    /// A file failed for good, or (with [`Error::Cancelled`]) was cancelled.
+    FileFailed { file: &'a str, error: &'a Error },
+    /// Every file is done; the report says how each went.
+    Finished { report: &'a DownloadReport },
+}
+
+/// Receives a download's [`ProgressEvent`]s (see
+/// [`super::ProgressMode::Sink`]). Events for different files arrive
+/// concurrently, from whichever task is fetching each.
+pub trait ProgressSink: Send + Sync {
+    fn event(&self, event: &ProgressEvent<'_>);
+}
+
+/// `indicatif` progress bars, one per file being fetched
+/// ([`super::ProgressMode::Auto`]).
+#[derive(Default)]
+pub struct Bars {
+    mp: MultiProgress,
+    bars: Mutex<HashMap<String, ProgressBar>>,
+}
+
+impl ProgressSink for Bars {
+    fn event(&self, event: &ProgressEvent<'_>) {
+        let mut bars = self.bars.lock().unwrap();
+        match *event {
+            ProgressEvent::FileStarted { file, size, done } => {
+                let bar = bars
+                    .entry(file.to_string())
+                    .or_insert_with(|| self.mp.add(new_bar()));
+                bar.set_length(size);
+                bar.set_position(done);
+                bar.set_message(file.to_string());
+            }
+            ProgressEvent::Advanced { file, bytes } => {
+                if let Some(bar) = bars.get(file) {
+                    bar.inc(bytes);
+                }
+            }
+            ProgressEvent::Retrying { file, .. } => {
+                // The first request of a file can fail before it started.
+                let bar = bars
+                    .entry(file.to_string())
+                    .or_insert_with(|| self.mp.add(new_bar()));
+                bar.set_message(format!("{file} (retrying)"));
+            }
+            ProgressEvent::FileFinished { file, .. } => {
+                if let Some(bar) = bars.remove(file) {
+                    bar.finish_with_message(format!("Downloaded: {file}"));
+                }
+            }
+            ProgressEvent::FileFailed { file, error } => {
+                if let Some(bar) = bars.remove(file) {
+                    let status = match error {
+                        Error::Cancelled => "Cancelled",
+                        _ => "Failed",
+                    };
+                    bar.abandon_with_message(format!("{status}: {file}"));
+                }
+            }
+            ProgressEvent::Planned { .. } | ProgressEvent::Finished { .. } => {}
+        }
+    }
+}
+
+fn new_bar() -> ProgressBar {
+    let pb = ProgressBar::new(0);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta}) - {msg}")
+            .expect("Failed to create ProgressBar template")
+            .progress_chars("#>-"),
+    );
+    pb
+}
+
+/// Drops every event ([`super::ProgressMode::Hidden`]).
+pub(crate) struct Silent;
+
+impl ProgressSink for Silent {
+    fn event(&self, _: &ProgressEvent<'_>) {}
+}
+
+/// One file's transfer, shared by all its attempts and segments: forwards
+/// its progress to the sink and counts the bytes fetched.
+pub(crate) struct FileProgress<'a> {
+    sink: &'a dyn ProgressSink,
+    file: &'a str,
+    fetched: AtomicU64,
+    endpoint: Mutex<Option<String>>,
+}
+
+impl<'a> FileProgress<'a> {
+    pub(crate) fn new(sink: &'a dyn ProgressSink, file: &'a str) -> Self {
+        Self {
+            sink,
+            file,
+            fetched: AtomicU64::new(0),
+            endpoint: Mutex::new(None),
+        }
+    }
+
+    /// The transfer (re)starts at `done` of `size` bytes (`done` is what's
+    /// already on disk, so it isn't counted as fetched).
+    pub(crate) fn start(&self, size: u64, done: u64) {
+        let file = self.file;
+        self.sink
+            .event(&ProgressEvent::FileStarted { file, size, done });
+    }
+
+    /// `bytes` more bytes arrived over the network.
+    pub(crate) fn advance(&self, bytes: u64) {
+        self.fetched.fetch_add(bytes, Ordering::Relaxed);
+        let file = self.file;
+        self.sink.event(&ProgressEvent::Advanced { file, bytes });
+    }
+
+    pub(crate) fn retrying(&self, attempt: u32, delay: Duration, error: &Error) {
+        self.sink.event(&ProgressEvent::Retrying {
+            file: self.file,
+            attempt,
+            delay,
+            error,
+        });
+    }
+
+    /// The bytes fetched so far, over every attempt.
+    pub(crate) fn fetched(&self) -> u64 {
+        self.fetched.load(Ordering::Relaxed)
+    }
+
+    /// A request for the file was answered by `endpoint`.
+    pub(crate) fn served_by(&self, endpoint: Option<String>) {
+        if endpoint.is_some() {
+            *self.endpoint.lock().unwrap() = endpoint;
+        }
+    }
+
+    /// The hub endpoint that last answered a request for the file.
+    pub(crate) fn endpoint(&self) -> Option<String> {
+        self.endpoint.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl ProgressSink for Recorder {
+        fn event(&self, event: &ProgressEvent<'_>) {
+            self.0.lock().unwrap().push(format!("{event:?}"));
+        }
+    }
+
+    #[test]
+    fn test_file_progress() {
+        let recorder = Recorder::default();
+        let progress = FileProgress::new(&recorder, "a.bin");
+        progress.start(10, 4);
+        progress.advance(3);
+        progress.advance(3);
+        assert_eq!(progress.fetched(), 6);
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                r#"FileStarted { file: "a.bin", size: 10, done: 4 }"#,
+                r#"Advanced { file: "a.bin", bytes: 3 }"#,
+                r#"Advanced { file: "a.bin", bytes: 3 }"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bars_show_retry_before_start() {
+        let bars = Bars::default();
+        let error = Error::Http {
+            status: reqwest::StatusCode::BAD_GATEWAY,
+            url: "u".to_string(),
+        };
+        bars.event(&ProgressEvent::Retrying {
+            file: "a.bin",
+            attempt: 1,
+            delay: Duration::from_secs(1),
+            error: &error,
+        });
+        assert_eq!(
+            bars.bars.lock().unwrap()["a.bin"].message(),
+            "a.bin (retrying)"
+        );
+        bars.event(&ProgressEvent::FileStarted {
+            file: "a.bin",
+            size: 10,
+            done: 0,
+        });
+        assert_eq!(bars.bars.lock().unwrap().len(), 1);
+    }
+
+    // The events of a download, with just enough of each to tell them apart.
+    #[derive(Default)]
+    struct Events(Mutex<Vec<String>>);
+
+    impl ProgressSink for Events {
+        fn event(&self, event: &ProgressEvent<'_>) {
+            let event = match *event {
+                ProgressEvent::Planned { plan } => format!("planned {}", plan.files.len()),
+                ProgressEvent::FileStarted { file, size, done } => {
+                    format!("started {file} {size} {done}")
+                }
+                ProgressEvent::Advanced { file, bytes } => format!("advanced {file} {bytes}"),
+                ProgressEvent::Retrying { file, attempt, .. } => {
+                    format!("retrying {file} {attempt}")
+                }
+                ProgressEvent::FileFinished { file, .. } => format!("finished {file}"),
+                ProgressEvent::FileFailed { file, .. } => format!("failed {file}"),
+                ProgressEvent::Finished { report } => format!("done {}", report.files.len()),
+            };
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_events() {
+        use crate::model::{download, DownloadRequest, ProgressMode, RetryPolicy};
+        use std::sync::Arc;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/models/test/model"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "sha": "abc123",
+                "siblings": [
+                    {"rfilename": "a.json", "size": 2},
+                    {"rfilename": "b.json", "size": 2}
+                ]
+            })))
+            .mount(&server)
+            .await;
+        // `a.json` is unavailable once, then served; `b.json` is gone.
+        Mock::given(method("GET"))
+            .and(path("/test/model/resolve/abc123/a.json"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/test/model/resolve/abc123/a.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/test/model/resolve/abc123/b.json"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let events = Arc::new(Events::default());
+        let report = download(&DownloadRequest {
+            repository: "test/model".to_string(),
+            to: dir.path().to_path_buf(),
+            api_base_url: server.uri(),
+            concurrency: 1,
+            progress: ProgressMode::Sink(events.clone()),
+            retry: RetryPolicy {
+                base_delay: Duration::from_millis(1),
+                jitter: 0.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert!(!report.is_success());
+        assert_eq!(
+            *events.0.lock().unwrap(),
+            vec![
+                "planned 2",
+                "retrying a.json 1",
+                "started a.json 2 0",
+                "advanced a.json 2",
+                "finished a.json",
+                "failed b.json",
+                "done 2",
+            ]
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! What a [`super::download`] did, file by file.
+
+use super::verify::Verification;
+use crate::{Error, Result};
+use serde::{Serialize, Serializer};
+use std::fmt::Display;
+use std::time::Duration;
+
+/// The outcome of a download: every selected file's fate, the bytes fetched
+/// and how long it took.
+#[derive(Debug, Serialize)]
+pub struct DownloadReport {
+    pub repository: String,
+    /// The revision asked for (`main` if none was).
+    pub revision: String,
+    /// The commit every file was fetched from, if the hub said.
+    pub commit: Option<String>,
+    /// The hub endpoints the files were fetched from (see
+    /// [`FileReport::endpoint`]), each once, in the order of the files;
+    /// empty if nothing was fetched.
+    pub endpoints: Vec<String>,
+    pub files: Vec<FileReport>,
+    /// Files deleted by [`super::DownloadRequest::prune`].
+    pub pruned: Vec<String>,
+    /// The bytes fetched over the network, over all files.
+    pub bytes: u64,
+    #[serde(rename = "duration_secs", serialize_with = "secs")]
+    pub duration: Duration,
+}
+
+/// One selected file of a [`DownloadReport`].
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    /// The file's path within the repository.
+    pub file: String,
+    #[serde(flatten)]
+    pub outcome: FileOutcome,
+    /// The bytes fetched over the network, over every attempt (not counting
+    /// a resumed prefix already on disk).
+    pub bytes: u64,
+    /// The hub endpoint that served the file (the last to answer, if a
+    /// request for it fell back to a mirror); `None` if nothing was asked
+    /// of the hub for it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(rename = "duration_secs", serialize_with = "secs")]
+    pub duration: Duration,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum FileOutcome {
+    /// Fetched, checked and put in place.
+    Downloaded {
+        #[serde(serialize_with = "display")]
+        verification: Verification,
+    },
+    /// Already in place ([`Verification::Cached`] or
+    /// [`Verification::UpToDate`]), so nothing was fetched.
+    Skipped {
+        #[serde(serialize_with = "display")]
+        verification: Verification,
+    },
+    Failed {
+        #[serde(serialize_with = "display")]
+        error: Error,
+    },
+    /// Stopped by [`super::DownloadRequest::cancel`], in flight or before it
+    /// started; a later download resumes it.
+    Cancelled,
+}
+
+impl FileOutcome {
+    pub(crate) fn of(result: Result<Verification>) -> Self {
+        match result {
+            Ok(verification @ (Verification::Cached | Verification::UpToDate)) => {
+                Self::Skipped { verification }
+            }
+            Ok(verification) => Self::Downloaded { verification },
+            Err(Error::Cancelled) => Self::Cancelled,
+            Err(error) => Self::Failed { error },
+        }
+    }
+}
+
+impl DownloadReport {
+    /// The bytes fetched per second, over the whole download.
+    pub fn throughput(&self) -> f64 {
+        per_second(self.bytes, self.duration)
+    }
+
+    pub fn downloaded(&self) -> impl Iterator<Item = &FileReport> {
+        self.files
+            .iter()
+            .filter(|f| matches!(f.outcome, FileOutcome::Downloaded { .. }))
+    }
+
+    pub fn skipped(&self) -> impl Iterator<Item = &FileReport> {
+        self.files
+            .iter()
+            .filter(|f| matches!(f.outcome, FileOutcome::Skipped { .. }))
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &FileReport> {
+        self.files
+            .iter()
+            .filter(|f| matches!(f.outcome, FileOutcome::Failed { .. }))
+    }
+
+    /// The files left unfinished because the download was cancelled.
+    pub fn cancelled(&self) -> impl Iterator<Item = &FileReport> {
+        self.files
+            .iter()
+            .filter(|f| matches!(f.outcome, FileOutcome::Cancelled))
+    }
+
+    /// Whether every file is in place.
+    pub fn is_success(&self) -> bool {
+        self.failed().chain(self.cancelled()).next().is_none()
+    }
+
+    /// The report, or [`Error::DownloadFailed`] with the failed files' errors
+    /// if any failed, or else [`Error::Cancelled`] if any were cancelled.
+    pub fn into_result(self) -> Result<Self> {
+        if self.is_success() {
+            return Ok(self);
+        }
+        if self.failed().next().is_none() {
+            return Err(Error::Cancelled);
+        }
+        let failures = self
+            .files
+            .into_iter()
+            .filter_map(|f| match f.outcome {
+                FileOutcome::Failed { error } => Some((f.file, error)),
+                _ => None,
+            })
+            .collect();
+        Err(Error::DownloadFailed {
+            repository: self.repository,
+            failures,
+        })
+    }
+}
+
+impl FileReport {
+    /// The bytes fetched per second for this file.
+    pub fn throughput(&self) -> f64 {
+        per_second(self.bytes, self.duration)
+    }
+}
+
+fn per_second(bytes: u64, duration: Duration) -> f64 {
+    match duration.as_secs_f64() {
+        0.0 => 0.0,
+        secs => bytes as f64 / secs,
+    }
+}
+
+fn secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+fn display<S: Serializer>(value: &impl Display, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, result: Result<Verification>, bytes: u64) -> FileReport {
+        FileReport {
+            file: name.to_string(),
+            outcome: FileOutcome::of(result),
+            bytes,
+            endpoint: None,
+            duration: Duration::from_secs(1),
+        }
+    }
+
+    fn report(files: Vec<FileReport>) -> DownloadReport {
+        DownloadReport {
+            repository: "test/model".to_string(),
+            revision: "main".to_string(),
+            commit: Some("abc123".to_string()),
+            endpoints: vec![],
+            bytes: files.iter().map(|f| f.bytes).sum(),
+            files,
+            pruned: vec![],
+            duration: Duration::from_secs(2),
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let report = report(vec![
+            file("a.json", Ok(Verification::GitSha1), 10),
+            file("b.json", Ok(Verification::UpToDate), 0),
+            file("c.json", Err(Error::InvalidResponse("x".to_string())), 4),
+        ]);
+        assert_eq!(report.downloaded().count(), 1);
+        assert_eq!(report.skipped().count(), 1);
+        assert_eq!(report.failed().count(), 1);
+        assert_eq!(report.throughput(), 7.0);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["files"][0]["outcome"], "downloaded");
+        assert_eq!(json["files"][0]["verification"], "git sha1 verified");
+        assert_eq!(json["files"][2]["error"], "unexpected response: x");
+        assert_eq!(json["duration_secs"], 2.0);
+
+        match report.into_result() {
+            Err(Error::DownloadFailed { failures, .. }) => assert_eq!(failures[0].0, "c.json"),
+            other => panic!("expected DownloadFailed, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_cancelled_report() {
+        let report = report(vec![
+            file("a.json", Ok(Verification::GitSha1), 10),
+            file("b.json", Err(Error::Cancelled), 4),
+        ]);
+        assert_eq!(report.cancelled().count(), 1);
+        assert_eq!(report.failed().count(), 0);
+        assert!(!report.is_success());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["files"][1]["outcome"], "cancelled");
+
+        assert!(matches!(report.into_result(), Err(Error::Cancelled)));
+    }
+}
@@ -0,0 +1,107 @@
+use std::hash::{BuildHasher, RandomState};
+use std::time::Duration;
+
+/// How [`super::download`] retries a file after a transient failure (see
+/// [`crate::Error::is_retryable`]).
+///
+/// Retries resume from the file's `.incomplete` temporary, so a retry only
+/// fetches what the failed attempt didn't.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Total attempts per file, including the first (`1` ⇒ never retry).
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for each retry after it.
+    pub base_delay: Duration,
+    /// Upper bound on the backoff delay.
+    pub max_delay: Duration,
+    /// Random extra delay as a fraction (`0.0..=1.0`) of the backoff, so
+    /// concurrent downloads don't retry in lockstep.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            jitter: 0.25,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The backoff before retry number `retry` (1-based), without jitter:
+    /// `base_delay * 2^(retry - 1)`, capped at `max_delay`.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    /// The delay before retry number `retry`: the server's `Retry-After` if
+    /// it sent one (capped at `max_delay`), otherwise the jittered backoff.
+    pub fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_delay);
+        }
+        let backoff = self.backoff(retry);
+        // `RandomState` is seeded randomly per instance: plenty of entropy
+        // for spreading retries out, without a dependency on `rand`.
+        let random = RandomState::new().hash_one(retry) as f64 / u64::MAX as f64;
+        backoff + backoff.mul_f64(self.jitter.clamp(0.0, 1.0) * random)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5),
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(4), Duration::from_secs(5));
+        assert_eq!(policy.backoff(64), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_delay_jitter_is_bounded() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_secs(2),
+            jitter: 0.5,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            let delay = policy.delay(1, None);
+            assert!(delay >= Duration::from_secs(2));
+            assert!(delay <= Duration::from_secs(3));
+        }
+    }
+
+    #[test]
+    fn test_delay_prefers_retry_after() {
+        let policy = RetryPolicy::default();
+        assert_eq!(
+            policy.delay(3, Some(Duration::from_secs(7))),
+            Duration::from_secs(7)
+        );
+    }
+
+    #[test]
+    fn test_delay_caps_retry_after() {
+        let policy = RetryPolicy {
+            max_delay: Duration::from_secs(60),
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(86400))),
+            Duration::from_secs(60)
+        );
+    }
+}
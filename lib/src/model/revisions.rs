@@ -1,21 +1,29 @@
-use crate::BoxError;
-use reqwest::Client;
+use crate::{Error, HubClient, Result};
 use serde_json::Value;
 
 /// Discover the revisions (branch names) of a Hugging Face repository.
-pub async fn revisions(repository: &str, api_base_url: &str) -> Result<Vec<String>, BoxError> {
-    let client = Client::new();
-    let url = build_revisions_url(repository, api_base_url);
-    let response = client.get(&url).send().await?;
-    if response.status().is_success() {
-        let refs: Value = response.json().await?;
-        Ok(branch_names(&refs))
-    } else {
-        Err(format!(
-            "Failed to fetch refs for '{repository}' (HTTP {})",
-            response.status()
-        )
-        .into())
+pub async fn revisions(
+    repository: &str,
+    token: Option<&str>,
+    api_base_url: &str,
+) -> Result<Vec<String>> {
+    HubClient::with_endpoint(api_base_url)?
+        .with_token(token)
+        .revisions(repository)
+        .await
+}
+
+impl HubClient {
+    /// Discover the revisions (branch names) of a Hugging Face repository.
+    pub async fn revisions(&self, repository: &str) -> Result<Vec<String>> {
+        let url = build_revisions_url(repository, self.endpoint());
+        let response = self.send(&url).await?;
+        if response.status().is_success() {
+            let refs: Value = response.json().await?;
+            Ok(branch_names(&refs))
+        } else {
+            Err(Error::from_response(&url, &response))
+        }
     }
 }
 
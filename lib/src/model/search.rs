@@ -1,44 +1,46 @@
-use crate::BoxError;
-use reqwest::Client;
-use serde_json::Value;
+use super::info::ModelInfo;
+use crate::{Error, HubClient, Result};
 
 /// Search for repositories by keyword(s) and optional filter; returns the
-/// matching `modelId`s.
+/// matching models (see [`model_ids`] for just their ids).
 pub async fn search(
     keywords: &[String],
     filter: Option<&str>,
+    token: Option<&str>,
     api_base_url: &str,
-) -> Result<Vec<String>, BoxError> {
-    let client = Client::new();
-    let url = build_search_url(keywords, filter, api_base_url);
-    let response = client.get(&url).send().await?;
-    if response.status().is_success() {
-        let models: Value = response.json().await?;
-        Ok(model_ids(&models))
-    } else {
-        let query = keywords.join(" ");
-        Err(format!(
-            "Failed to search models with keyword '{query}' (HTTP {})",
-            response.status()
-        )
-        .into())
+) -> Result<Vec<ModelInfo>> {
+    HubClient::with_endpoint(api_base_url)?
+        .with_token(token)
+        .search(keywords, filter)
+        .await
+}
+
+impl HubClient {
+    /// Search for repositories by keyword(s) and optional filter; returns the
+    /// matching models.
+    pub async fn search(
+        &self,
+        keywords: &[String],
+        filter: Option<&str>,
+    ) -> Result<Vec<ModelInfo>> {
+        let url = build_search_url(keywords, filter, self.endpoint());
+        let response = self.send(&url).await?;
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            Err(Error::from_response(&url, &response))
+        }
     }
 }
 
-/// Extract `modelId`s from a search response array.
-pub fn model_ids(models: &Value) -> Vec<String> {
+/// The repository ids of search results (skipping any without one).
+pub fn model_ids(models: &[ModelInfo]) -> Vec<String> {
     models
-        .as_array()
-        .map(|list| {
-            list.iter()
-                .filter_map(|m| {
-                    m.get("modelId")
-                        .and_then(|id| id.as_str())
-                        .map(String::from)
-                })
-                .collect()
-        })
-        .unwrap_or_default()
+        .iter()
+        .map(|m| m.repo_id())
+        .filter(|id| !id.is_empty())
+        .map(String::from)
+        .collect()
 }
 
 pub fn build_search_url(keywords: &[String], filter: Option<&str>, api_base_url: &str) -> String {
@@ -104,9 +106,8 @@ mod tests {
 
     #[test]
     fn test_model_ids() {
-        let json: Value =
-            serde_json::from_str(r#"[{"modelId": "a/b"}, {"modelId": "c/d"}, {"other": "x"}]"#)
-                .unwrap();
-        assert_eq!(model_ids(&json), vec!["a/b", "c/d"]);
+        let models: Vec<ModelInfo> =
+            serde_json::from_str(r#"[{"modelId": "a/b"}, {"id": "c/d"}, {"other": "x"}]"#).unwrap();
+        assert_eq!(model_ids(&models), vec!["a/b", "c/d"]);
     }
 }
@@ -0,0 +1,257 @@
+//! Parallel byte-range download of a single large file.
+//!
+//! The `.incomplete` temporary is pre-allocated to the file's full size and
+//! each segment is fetched with its own `Range` request and written at its
+//! offset. The segments finished so far are recorded in the temporary's
+//! `.incomplete.json` state, so a retry or a later run only fetches the rest.
+
+use super::download::{
+    header_str, parse_content_range, read_state, send_get, throttle, until_cancelled,
+    DownloadRequest,
+};
+use super::progress::FileProgress;
+use crate::{Error, HubClient};
+use futures::stream::{StreamExt, TryStreamExt};
+use reqwest::header::{CONTENT_RANGE, ETAG};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::io::{self, SeekFrom};
+use std::path::Path;
+use std::sync::Mutex;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+/// Why a segmented download stopped.
+#[derive(Debug)]
+pub(super) enum SegmentError {
+    /// The server can't (or won't any more) serve this file in segments: it
+    /// ignored a range request, or the file changed between segments. The
+    /// caller should start over on a single connection.
+    Abandoned(String),
+    /// An ordinary failure, to be retried or reported like any other.
+    Failed(Error),
+}
+
+impl<E: Into<Error>> From<E> for SegmentError {
+    fn from(e: E) -> Self {
+        Self::Failed(e.into())
+    }
+}
+
+/// Split `size` bytes into consecutive inclusive `(start, end)` ranges of at
+/// most `segment_size` bytes.
+pub fn segment_ranges(size: u64, segment_size: u64) -> Vec<(u64, u64)> {
+    let segment_size = segment_size.max(1);
+    (0..size)
+        .step_by(segment_size as usize)
+        .map(|start| (start, (start + segment_size).min(size) - 1))
+        .collect()
+}
+
+// What's recorded in the state file while a segmented download is underway.
+struct Progress {
+    etag: Option<String>,
+    done: Vec<(u64, u64)>,
+}
+
+/// Fill `tmp_path` with the `size` bytes of `url`, fetching up to
+/// `req.connections_per_file` segments at once.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn download_segments(
+    hub: &HubClient,
+    req: &DownloadRequest,
+    file: &str,
+    url: &str,
+    size: u64,
+    tmp_path: &Path,
+    state_path: &Path,
+    file_progress: &FileProgress<'_>,
+) -> Result<(), SegmentError> {
+    let state = read_state(state_path).await;
+    let on_disk = tokio::fs::metadata(tmp_path).await.map(|m| m.len()).ok();
+    let resumable = state["size"].as_u64() == Some(size)
+        && state["segment_size"].as_u64() == Some(req.segment_size)
+        && on_disk == Some(size);
+
+    let progress = if resumable {
+        Progress {
+            etag: state["etag"].as_str().map(String::from),
+            done: done_segments(&state),
+        }
+    } else {
+        let tmp = tokio::fs::File::create(tmp_path).await?;
+        tmp.set_len(size).await?;
+        Progress {
+            etag: None,
+            done: vec![],
+        }
+    };
+
+    let remaining: Vec<(u64, u64)> = segment_ranges(size, req.segment_size)
+        .into_iter()
+        .filter(|range| !progress.done.contains(range))
+        .collect();
+    let downloaded: u64 = progress.done.iter().map(|(s, e)| e - s + 1).sum();
+    if downloaded > 0 {
+        tracing::info!(
+            "{file}: resuming {} of {} segment(s)",
+            remaining.len(),
+            remaining.len() + progress.done.len()
+        );
+    }
+    file_progress.start(size, downloaded);
+
+    let progress = Mutex::new(progress);
+    save_progress(state_path, size, req.segment_size, &progress)?;
+
+    futures::stream::iter(remaining.into_iter().map(|range| {
+        let progress = &progress;
+        async move {
+            fetch_segment(
+                hub,
+                req,
+                file,
+                url,
+                size,
+                range,
+                tmp_path,
+                file_progress,
+                progress,
+            )
+            .await?;
+            progress.lock().unwrap().done.push(range);
+            save_progress(state_path, size, req.segment_size, progress)
+        }
+    }))
+    .buffer_unordered(req.connections_per_file.max(1))
+    .try_collect::<Vec<()>>()
+    .await?;
+
+    Ok(())
+}
+
+// Fetch the inclusive byte range `start..=end` and write it at `start`.
+#[allow(clippy::too_many_arguments)]
+async fn fetch_segment(
+    hub: &HubClient,
+    req: &DownloadRequest,
+    file: &str,
+    url: &str,
+    size: u64,
+    (start, end): (u64, u64),
+    tmp_path: &Path,
+    file_progress: &FileProgress<'_>,
+    progress: &Mutex<Progress>,
+) -> Result<(), SegmentError> {
+    let etag = progress.lock().unwrap().etag.clone();
+    let response = until_cancelled(
+        &req.cancel,
+        send_get(hub, file_progress, url, start, Some(end), etag.as_deref()),
+    )
+    .await??;
+
+    let status = response.status();
+    if status != StatusCode::PARTIAL_CONTENT {
+        if status.is_success() {
+            return Err(SegmentError::Abandoned(format!(
+                "{file}: server answered a range request with HTTP {status}"
+            )));
+        }
+        return Err(Error::from_response(url, &response).into());
+    }
+    let content_range =
+        header_str(&response, CONTENT_RANGE).and_then(|range| parse_content_range(&range));
+    if content_range != Some(((start, end), Some(size))) {
+        return Err(SegmentError::Abandoned(format!(
+            "{file}: unexpected Content-Range for bytes {start}-{end}/{size}"
+        )));
+    }
+    if let Some(now) = header_str(&response, ETAG) {
+        let mut progress = progress.lock().unwrap();
+        match &progress.etag {
+            Some(then) if *then != now => {
+                return Err(SegmentError::Abandoned(format!(
+                    "{file}: changed on the server mid-download"
+                )));
+            }
+            Some(_) => {}
+            None => progress.etag = Some(now),
+        }
+    }
+
+    let mut dest = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(tmp_path)
+        .await?;
+    dest.seek(SeekFrom::Start(start)).await?;
+    let mut remaining = end - start + 1;
+    let mut content = response.bytes_stream();
+    while let Some(chunk) = until_cancelled(&req.cancel, content.next()).await? {
+        let chunk = chunk?;
+        let len = chunk.len() as u64;
+        if len > remaining {
+            return Err(Error::InvalidResponse(format!(
+                "{file}: server sent more than bytes {start}-{end}"
+            ))
+            .into());
+        }
+        dest.write_all(&chunk).await?;
+        remaining -= len;
+        file_progress.advance(len);
+        throttle(req, len).await?;
+    }
+    dest.flush().await?;
+    if remaining > 0 {
+        let short = format!("{file}: bytes {start}-{end} ended {remaining} byte(s) short");
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, short).into());
+    }
+    Ok(())
+}
+
+pub(super) fn done_segments(state: &Value) -> Vec<(u64, u64)> {
+    state["segments"]
+        .as_array()
+        .map(|segments| {
+            segments
+                .iter()
+                .filter_map(|s| Some((s[0].as_u64()?, s[1].as_u64()?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn save_progress(
+    state_path: &Path,
+    size: u64,
+    segment_size: u64,
+    progress: &Mutex<Progress>,
+) -> Result<(), SegmentError> {
+    let progress = progress.lock().unwrap();
+    let state = json!({
+        "etag": progress.etag,
+        "size": size,
+        "segment_size": segment_size,
+        "segments": progress.done.iter().map(|(s, e)| [s, e]).collect::<Vec<_>>(),
+    });
+    std::fs::write(state_path, state.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segment_ranges() {
+        assert_eq!(segment_ranges(10, 4), vec![(0, 3), (4, 7), (8, 9)]);
+        assert_eq!(segment_ranges(8, 4), vec![(0, 3), (4, 7)]);
+        assert_eq!(segment_ranges(3, 4), vec![(0, 2)]);
+        assert_eq!(segment_ranges(0, 4), Vec::<(u64, u64)>::new());
+    }
+
+    #[test]
+    fn test_done_segments() {
+        let state = json!({"segments": [[0, 3], [8, 9]]});
+        assert_eq!(done_segments(&state), vec![(0, 3), (8, 9)]);
+        assert_eq!(done_segments(&json!({"etag": "x"})), vec![]);
+    }
+}
@@ -0,0 +1,158 @@
+//! The `.possum.json` sidecar kept in a flat download directory.
+//!
+//! It records the repository and commit the files were downloaded from, and
+//! the size, digest and modification time of every file [`super::download`]
+//! put there, so a later run can tell an unchanged file is up to date from
+//! its metadata alone instead of hashing it again.
+
+use super::info::Sibling;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// The sidecar's file name within a download directory.
+pub const SIDECAR_FILE: &str = ".possum.json";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Sidecar {
+    /// The repository the directory was downloaded from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    /// The revision last downloaded (e.g. `main`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    /// The commit that revision resolved to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// What was downloaded, by path within the repository.
+    #[serde(default)]
+    pub files: BTreeMap<String, FileRecord>,
+    /// Every file of the commit as the hub listed it, downloaded or not, so
+    /// an offline download can select from it (see
+    /// [`super::DownloadRequest::offline`]).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub listing: Vec<Sibling>,
+}
+
+/// A downloaded file as it was when it was put in place.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRecord {
+    pub size: u64,
+    /// The hex sha256 or git blob id it was verified against, if any.
+    pub digest: Option<String>,
+    /// Its modification time, as (seconds, nanoseconds) since the epoch.
+    pub modified: Option<(u64, u32)>,
+}
+
+impl FileRecord {
+    /// A record of the file at `path`, verified against `digest`.
+    pub fn of(path: &Path, digest: Option<&str>) -> Result<Self> {
+        let meta = fs::metadata(path)?;
+        Ok(Self {
+            size: meta.len(),
+            digest: digest.map(String::from),
+            modified: modified(&meta),
+        })
+    }
+
+    /// Whether the file at `meta` is still the one recorded, and was
+    /// verified against `digest`.
+    pub fn matches(&self, meta: &fs::Metadata, digest: &str) -> bool {
+        self.size == meta.len()
+            && self.modified.is_some()
+            && self.modified == modified(meta)
+            && self.digest.as_deref() == Some(digest)
+    }
+}
+
+fn modified(meta: &fs::Metadata) -> Option<(u64, u32)> {
+    let since_epoch = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((since_epoch.as_secs(), since_epoch.subsec_nanos()))
+}
+
+impl Sidecar {
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(SIDECAR_FILE)
+    }
+
+    /// The sidecar of `dir`; empty if there is none (or it's unreadable).
+    pub fn load(dir: &Path) -> Self {
+        fs::read(Self::path(dir))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// The sidecar of `dir` if it was written for `repository`; empty
+    /// otherwise, so nothing another repository left there (or that
+    /// predates the sidecar naming its repository) is trusted.
+    pub fn load_for(dir: &Path, repository: &str) -> Self {
+        let sidecar = Self::load(dir);
+        if sidecar.repository.as_deref() == Some(repository) {
+            sidecar
+        } else {
+            Self::default()
+        }
+    }
+
+    /// Write the sidecar of `dir`, replacing any there in one step so a
+    /// crash mid-write can't leave it truncated.
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).expect("a sidecar serializes");
+        let path = Self::path(dir);
+        let tmp_path = dir.join(format!("{SIDECAR_FILE}.tmp"));
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_matches_until_modified() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        fs::write(&path, "{}").unwrap();
+
+        let record = FileRecord::of(&path, Some("abc")).unwrap();
+        assert!(record.matches(&fs::metadata(&path).unwrap(), "abc"));
+        assert!(!record.matches(&fs::metadata(&path).unwrap(), "def"));
+
+        fs::write(&path, "{ }").unwrap();
+        assert!(!record.matches(&fs::metadata(&path).unwrap(), "abc"));
+    }
+
+    #[test]
+    fn test_load_and_save() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Sidecar::load(dir.path()), Sidecar::default());
+
+        let mut sidecar = Sidecar {
+            repository: Some("test/model".to_string()),
+            commit: Some("abc123".to_string()),
+            ..Default::default()
+        };
+        sidecar.files.insert(
+            "a.json".to_string(),
+            FileRecord {
+                size: 2,
+                digest: Some("abc".to_string()),
+                modified: Some((1, 2)),
+            },
+        );
+        sidecar.save(dir.path()).unwrap();
+        assert_eq!(Sidecar::load(dir.path()), sidecar);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert_eq!(Sidecar::load_for(dir.path(), "test/model"), sidecar);
+        assert_eq!(
+            Sidecar::load_for(dir.path(), "other/model"),
+            Sidecar::default()
+        );
+    }
+}
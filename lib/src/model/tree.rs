@@ -0,0 +1,204 @@
+use super::info::{LfsInfo, Sibling};
+use crate::{Error, HubClient, Result};
+use reqwest::header::LINK;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// One entry of a repository tree listing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TreeEntry {
+    #[serde(rename = "type")]
+    pub kind: EntryKind,
+    /// The entry's path within the repository.
+    pub path: String,
+    /// The file's size in bytes (`0` for a directory).
+    #[serde(default)]
+    pub size: u64,
+    /// The git object id: the blob id of a file (of its LFS pointer, for an
+    /// LFS file), the tree id of a directory.
+    pub oid: String,
+    /// Present for files stored in LFS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lfs: Option<LfsInfo>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl TreeEntry {
+    pub fn is_file(&self) -> bool {
+        self.kind == EntryKind::File
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    File,
+    Directory,
+}
+
+/// List every file and directory of a repository revision, with sizes.
+pub async fn list_tree(
+    repository: &str,
+    revision: Option<&str>,
+    token: Option<&str>,
+    api_base_url: &str,
+) -> Result<Vec<TreeEntry>> {
+    HubClient::with_endpoint(api_base_url)?
+        .with_token(token)
+        .list_tree(repository, revision)
+        .await
+}
+
+impl HubClient {
+    /// List every file and directory of a repository revision, with sizes,
+    /// following the tree API's pages to the end.
+    pub async fn list_tree(
+        &self,
+        repository: &str,
+        revision: Option<&str>,
+    ) -> Result<Vec<TreeEntry>> {
+        let mut url = build_tree_url(repository, revision, self.endpoint());
+        let mut entries = Vec::new();
+        loop {
+            tracing::debug!("Tree URL: {url}");
+            let response = self.send(&url).await?;
+            if !response.status().is_success() {
+                return Err(Error::from_response(&url, &response));
+            }
+            let next = response
+                .headers()
+                .get(LINK)
+                .and_then(|v| v.to_str().ok())
+                .and_then(next_link);
+            let page: Vec<TreeEntry> = response.json().await?;
+            entries.extend(page);
+            match next {
+                Some(next) => url = next,
+                None => return Ok(entries),
+            }
+        }
+    }
+}
+
+/// The tree listing of `siblings` (e.g. listed offline by
+/// [`super::list_download_files`]): an entry for each file, and one for each
+/// directory they're in. Without the hub there are no tree ids, and a file's
+/// `oid` is its blob id if known.
+pub fn tree_of(siblings: &[Sibling]) -> Vec<TreeEntry> {
+    let mut dirs = std::collections::BTreeSet::new();
+    let mut entries = vec![];
+    for sibling in siblings {
+        let mut path = sibling.rfilename.as_str();
+        while let Some((dir, _)) = path.rsplit_once('/') {
+            dirs.insert(dir);
+            path = dir;
+        }
+        entries.push(TreeEntry {
+            kind: EntryKind::File,
+            path: sibling.rfilename.clone(),
+            size: sibling.size.unwrap_or_default(),
+            oid: sibling.blob_id.clone().unwrap_or_default(),
+            lfs: sibling.lfs.clone(),
+            extra: Map::new(),
+        });
+    }
+    entries.extend(dirs.into_iter().map(|dir| TreeEntry {
+        kind: EntryKind::Directory,
+        path: dir.to_string(),
+        size: 0,
+        oid: String::new(),
+        lfs: None,
+        extra: Map::new(),
+    }));
+    entries
+}
+
+pub fn build_tree_url(repository: &str, revision: Option<&str>, api_base_url: &str) -> String {
+    let revision = revision.unwrap_or("main");
+    format!("{api_base_url}/api/models/{repository}/tree/{revision}?recursive=true")
+}
+
+/// The `rel="next"` target of a `Link` header, if any.
+pub fn next_link(link: &str) -> Option<String> {
+    link.split(',').find_map(|part| {
+        let (target, params) = part.split_once(';')?;
+        let is_next = params
+            .split(';')
+            .any(|param| matches!(param.trim(), "rel=\"next\"" | "rel=next"));
+        let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+        is_next.then(|| target.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_tree_url() {
+        assert_eq!(
+            build_tree_url("openai-community/gpt2", None, "https://huggingface.co"),
+            "https://huggingface.co/api/models/openai-community/gpt2/tree/main?recursive=true"
+        );
+        assert_eq!(
+            build_tree_url("test/model", Some("v1"), "http://localhost:8080"),
+            "http://localhost:8080/api/models/test/model/tree/v1?recursive=true"
+        );
+    }
+
+    #[test]
+    fn test_next_link() {
+        assert_eq!(
+            next_link("<https://huggingface.co/api/models/a/tree/main?cursor=xyz>; rel=\"next\""),
+            Some("https://huggingface.co/api/models/a/tree/main?cursor=xyz".to_string())
+        );
+        assert_eq!(
+            next_link("<https://x/prev>; rel=\"prev\", <https://x/next>; rel=\"next\""),
+            Some("https://x/next".to_string())
+        );
+        assert_eq!(next_link("<https://x/prev>; rel=\"prev\""), None);
+    }
+
+    #[test]
+    fn test_tree_entry() {
+        let entries: Vec<TreeEntry> = serde_json::from_str(
+            r#"[
+                {"type": "directory", "oid": "d1", "size": 0, "path": "onnx"},
+                {"type": "file", "oid": "b1", "size": 665, "path": "config.json"},
+                {"type": "file", "oid": "b2", "size": 548105171, "path": "model.safetensors",
+                 "lfs": {"oid": "aa", "size": 548105171, "pointerSize": 135}}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(entries[0].kind, EntryKind::Directory);
+        assert!(entries[1].is_file());
+        assert_eq!(entries[1].size, 665);
+        assert_eq!(entries[2].lfs.as_ref().unwrap().sha256, "aa");
+    }
+
+    #[test]
+    fn test_tree_of() {
+        let sibling = |file: &str, size| Sibling {
+            rfilename: file.to_string(),
+            size: Some(size),
+            ..Default::default()
+        };
+        let entries = tree_of(&[
+            sibling("config.json", 2),
+            sibling("onnx/fp16/model.onnx", 9),
+        ]);
+        let paths: Vec<(&str, EntryKind)> =
+            entries.iter().map(|e| (e.path.as_str(), e.kind)).collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("config.json", EntryKind::File),
+                ("onnx/fp16/model.onnx", EntryKind::File),
+                ("onnx", EntryKind::Directory),
+                ("onnx/fp16", EntryKind::Directory),
+            ]
+        );
+        assert_eq!(entries[1].size, 9);
+    }
+}
@@ -0,0 +1,223 @@
+use super::info::Sibling;
+use crate::{Error, Result};
+use serde::Serialize;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// The content digest the Hub publishes for a file.
+///
+/// LFS files are identified by the sha256 of their contents; regular git
+/// files by their git blob id (the sha1 of `blob {size}\0` followed by the
+/// contents).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpectedDigest {
+    Sha256(String),
+    GitSha1(String),
+}
+
+impl ExpectedDigest {
+    /// The expected digest of a `siblings` entry from the model info
+    /// (requested with `?blobs=true`): `lfs.sha256` if the file is in LFS,
+    /// its `blobId` otherwise.
+    pub fn from_sibling(sibling: &Sibling) -> Option<Self> {
+        if let Some(lfs) = &sibling.lfs {
+            return Some(Self::Sha256(lfs.sha256.to_ascii_lowercase()));
+        }
+        sibling
+            .blob_id
+            .as_ref()
+            .map(|sha1| Self::GitSha1(sha1.to_ascii_lowercase()))
+    }
+
+    /// The expected digest carried by an `X-Linked-Etag` response header:
+    /// 64 hex digits are a sha256, 40 a git blob id.
+    pub fn from_etag(etag: &str) -> Option<Self> {
+        let etag = etag.trim().trim_start_matches("W/").trim_matches('"');
+        if !etag.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        match etag.len() {
+            64 => Some(Self::Sha256(etag.to_ascii_lowercase())),
+            40 => Some(Self::GitSha1(etag.to_ascii_lowercase())),
+            _ => None,
+        }
+    }
+
+    pub fn hex(&self) -> &str {
+        match self {
+            Self::Sha256(hex) | Self::GitSha1(hex) => hex,
+        }
+    }
+}
+
+/// How a downloaded file was checked against the Hub before it was
+/// renamed into place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    /// Contents matched the LFS sha256.
+    Sha256,
+    /// Contents matched the git blob id.
+    GitSha1,
+    /// The Hub published no digest for the file, so nothing was checked.
+    Unverified,
+    /// The blob was already in the hub cache under its digest, so nothing
+    /// was fetched.
+    Cached,
+    /// An identical file was already in the download directory, so nothing
+    /// was fetched.
+    UpToDate,
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sha256 => write!(f, "sha256 verified"),
+            Self::GitSha1 => write!(f, "git sha1 verified"),
+            Self::Unverified => write!(f, "unverified"),
+            Self::Cached => write!(f, "already cached"),
+            Self::UpToDate => write!(f, "already up to date"),
+        }
+    }
+}
+
+/// Incrementally hashes a file's contents for comparison with an
+/// [`ExpectedDigest`].
+pub(crate) enum ContentHasher {
+    Sha256(Sha256),
+    GitSha1(Sha1),
+}
+
+impl ContentHasher {
+    /// A hasher for `expected`; a git blob id needs the file's total `size`
+    /// up front for its header.
+    pub(crate) fn new(expected: &ExpectedDigest, size: u64) -> Self {
+        match expected {
+            ExpectedDigest::Sha256(_) => Self::Sha256(Sha256::new()),
+            ExpectedDigest::GitSha1(_) => {
+                let mut hasher = Sha1::new();
+                hasher.update(format!("blob {size}\0").as_bytes());
+                Self::GitSha1(hasher)
+            }
+        }
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Self::Sha256(h) => h.update(bytes),
+            Self::GitSha1(h) => h.update(bytes),
+        }
+    }
+
+    pub(crate) fn finalize_hex(self) -> String {
+        match self {
+            Self::Sha256(h) => format!("{:x}", h.finalize()),
+            Self::GitSha1(h) => format!("{:x}", h.finalize()),
+        }
+    }
+}
+
+/// Compare `file`'s finished hash against `expected`, returning the verdict
+/// or an [`Error::Integrity`].
+pub(crate) fn check(file: &str, expected: &ExpectedDigest, actual: &str) -> Result<Verification> {
+    let (algorithm, verdict) = match expected {
+        ExpectedDigest::Sha256(_) => ("sha256", Verification::Sha256),
+        ExpectedDigest::GitSha1(_) => ("git sha1", Verification::GitSha1),
+    };
+    if expected.hex() == actual {
+        Ok(verdict)
+    } else {
+        Err(Error::Integrity {
+            file: file.to_string(),
+            algorithm,
+            expected: expected.hex().to_string(),
+            actual: actual.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(expected: &ExpectedDigest, content: &[u8]) -> String {
+        let mut hasher = ContentHasher::new(expected, content.len() as u64);
+        hasher.update(content);
+        hasher.finalize_hex()
+    }
+
+    #[test]
+    fn test_from_sibling_prefers_lfs_sha256() {
+        let sibling: Sibling = serde_json::from_str(
+            r#"{"rfilename": "model.safetensors", "blobId": "aa",
+                "lfs": {"sha256": "BB", "size": 1}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ExpectedDigest::from_sibling(&sibling),
+            Some(ExpectedDigest::Sha256("bb".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_from_sibling_blob_id() {
+        let sibling: Sibling =
+            serde_json::from_str(r#"{"rfilename": "config.json", "blobId": "aa"}"#).unwrap();
+        assert_eq!(
+            ExpectedDigest::from_sibling(&sibling),
+            Some(ExpectedDigest::GitSha1("aa".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_from_sibling_without_digest() {
+        let sibling: Sibling = serde_json::from_str(r#"{"rfilename": "config.json"}"#).unwrap();
+        assert_eq!(ExpectedDigest::from_sibling(&sibling), None);
+    }
+
+    #[test]
+    fn test_from_etag() {
+        let sha256 = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        assert_eq!(
+            ExpectedDigest::from_etag(&format!("\"{sha256}\"")),
+            Some(ExpectedDigest::Sha256(sha256.to_string()))
+        );
+        assert_eq!(
+            ExpectedDigest::from_etag("W/\"e69de29bb2d1d6434b8b29ae775ad8c2e48c5391\""),
+            Some(ExpectedDigest::GitSha1(
+                "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391".to_string()
+            ))
+        );
+        assert_eq!(ExpectedDigest::from_etag("\"1234-abcd\""), None);
+    }
+
+    #[test]
+    fn test_sha256_of_empty_file() {
+        let expected = ExpectedDigest::Sha256(String::new());
+        assert_eq!(
+            hash(&expected, b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_git_sha1_matches_git_hash_object() {
+        // `printf 'hello\n' | git hash-object --stdin`
+        let expected = ExpectedDigest::GitSha1(String::new());
+        assert_eq!(
+            hash(&expected, b"hello\n"),
+            "ce013625030ba8dba906f756967f9e9ca394464a"
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let expected = ExpectedDigest::GitSha1("abc".to_string());
+        assert_eq!(check("a", &expected, "abc").unwrap(), Verification::GitSha1);
+        assert!(matches!(
+            check("a", &expected, "def"),
+            Err(Error::Integrity { .. })
+        ));
+    }
+}